@@ -1,23 +1,556 @@
-use petgraph::dot::{Config, Dot};
 use petgraph::graph::DiGraph;
+use regex::Regex;
 use rust_code_visualizer::{
-    add_edges_for_graph, create_nodes_for_graph, filter_structs, from_item_to_structs,
-    parse_rust_code,
+    add_edges_for_graph_with_options, api_changes, apply_cfg, apply_cfg_to_files, check_rules,
+    crate_root, create_nodes_for_graph, diff_models, diff_to_dot, er_diagram, er_to_dot,
+    er_to_mermaid, external_crates, feature_diff, filter_model, find_cycles, find_leaks,
+    find_manifest, find_references, focus_graph, json_schema, load_files, load_model,
+    locate_violations, module_graph, module_graph_to_dot, public_api, required_bump, serde_view,
+    state_machine, state_machine_to_dot, state_machine_to_mermaid, stats, stats_to_csv,
+    stats_to_json, stats_to_table, to_dot, to_mermaid, to_plantuml, CargoFeatures, CfgConfig,
+    Cycle, Direction, Edge, ExportOptions, Filters, Focus, GraphOptions, Impact, Model, ModelDiff,
+    Rules,
 };
 use std::env;
-use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use syn::File;
+
+const USAGE: &str = "\
+Usage:
+    rcvis [options] <file.rs>
+    rcvis json-schema <Type> [options] <file.rs>
+    rcvis er [--format dot|mermaid] [options] <file.rs>
+    rcvis states <Enum> [--format dot|mermaid] [options] <file.rs>
+    rcvis modules [options] <file.rs>
+    rcvis uses <Type> [options] <file.rs>
+    rcvis cycles [options] <file.rs>
+    rcvis leaks [options] <file.rs>
+    rcvis check --rules rcvis.toml [options] <file.rs>
+    rcvis feature-diff <feature> [--no-default-features] [--manifest-path Cargo.toml] <file.rs>
+    rcvis diff [--format dot|text] [options] <old> <new>
+    rcvis breaking [options] <old> <new>
+    rcvis stats [--format text|csv|json] [options] <file.rs>
+
+Options:
+    --bindings              node for every instantiation of a local generic type
+    --external              leaf node for every non-local type (DateTime, Uuid, String)
+    --group-external        like --external, grouped by the crate they come from
+    --primitives            leaf node for every primitive type (u64, bool, str)
+    --members               list fields, variants and associated types in the nodes
+    --clusters              group types by module
+    --highlight-cycles      draw the edges of recursive types in red
+    --public                only the public API
+    --include GLOB          only types whose path matches, like crate::domain::*
+    --exclude REGEX         drop types whose name or path matches, like '.*Dto$'
+    --exclude-module PATH   drop the types of every module named PATH, like tests
+    --filtered-edges drop|collapse
+                            drop fields pointing to filtered types (default), or point them
+                            to a single external node
+    --focus Type            only the types within --depth hops (1 by default) of Type
+    --depth N
+    --direction out|in|both follow edges from Type (out), to it (in) or both (default)
+    --serde                 fields and variants as serialized by serde
+    --features a,b          enabled Cargo features for #[cfg]
+    --cfg name[=value]      enabled cfg option, can be repeated
+    --no-test               drop #[cfg(test)] items
+    --format FORMAT         dot (default), mermaid, plantuml, text (diffs, stats), csv or
+                            json (stats)";
+
+#[derive(Default, Clone, Copy, PartialEq)]
+enum Format {
+    #[default]
+    Dot,
+    Mermaid,
+    PlantUml,
+    Text,
+    Csv,
+    Json,
+}
+
+/// Flags shared by all commands, everything else is collected in `positional`
+#[derive(Default)]
+struct Options {
+    positional: Vec<String>,
+    graph: GraphOptions,
+    export: ExportOptions,
+    cfg: CfgConfig,
+    public_only: bool,
+    serde: bool,
+    no_default_features: bool,
+    manifest_path: Option<PathBuf>,
+    rules: Option<PathBuf>,
+    /// `None` when not given, each command has its own default
+    format: Option<Format>,
+    filters: Filters,
+    collapse_filtered: bool,
+    group_external: bool,
+    highlight_cycles: bool,
+    focus: Option<String>,
+    depth: Option<usize>,
+    direction: Direction,
+}
+
+fn parse_options(args: impl IntoIterator<Item = String>) -> Options {
+    let mut args = args.into_iter();
+    let mut options = Options::default();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--bindings" => options.graph.synthesize_bindings = true,
+            "--external" => options.graph.external_types = true,
+            "--group-external" => {
+                options.graph.external_types = true;
+                options.group_external = true;
+            }
+            "--primitives" => options.graph.primitive_types = true,
+            "--members" => options.export.members = true,
+            "--clusters" => options.export.clusters = true,
+            "--highlight-cycles" => options.highlight_cycles = true,
+            "--public" => options.public_only = true,
+            "--serde" => options.serde = true,
+            "--features" => {
+                let features = args.next().unwrap_or_else(|| usage());
+                options.cfg.features = Some(
+                    features
+                        .split(',')
+                        .filter(|feature| !feature.is_empty())
+                        .map(String::from)
+                        .collect(),
+                );
+            }
+            "--cfg" => {
+                let cfg = args.next().unwrap_or_else(|| usage()).replace('"', "");
+                options.cfg.cfgs.get_or_insert_with(Vec::new).push(cfg);
+            }
+            "--no-test" => options.cfg.test = Some(false),
+            "--no-default-features" => options.no_default_features = true,
+            "--manifest-path" => {
+                options.manifest_path = Some(args.next().unwrap_or_else(|| usage()).into())
+            }
+            "--rules" => options.rules = Some(args.next().unwrap_or_else(|| usage()).into()),
+            "--format" => {
+                options.format = Some(match args.next().as_deref() {
+                    Some("dot") => Format::Dot,
+                    Some("mermaid") => Format::Mermaid,
+                    Some("plantuml") => Format::PlantUml,
+                    Some("text") => Format::Text,
+                    Some("csv") => Format::Csv,
+                    Some("json") => Format::Json,
+                    _ => usage(),
+                })
+            }
+            "--focus" => options.focus = Some(args.next().unwrap_or_else(|| usage())),
+            "--depth" => {
+                let depth = args.next().and_then(|depth| depth.parse().ok());
+                options.depth = Some(depth.unwrap_or_else(|| usage()));
+            }
+            "--direction" => {
+                options.direction = match args.next().as_deref() {
+                    Some("out") => Direction::Out,
+                    Some("in") => Direction::In,
+                    Some("both") => Direction::Both,
+                    _ => usage(),
+                }
+            }
+            "--include" => options
+                .filters
+                .include
+                .push(args.next().unwrap_or_else(|| usage())),
+            "--exclude" => {
+                let pattern = args.next().unwrap_or_else(|| usage());
+                let regex = Regex::new(&pattern).unwrap_or_else(|err| fail(err));
+                options.filters.exclude.push(regex);
+            }
+            "--exclude-module" => options
+                .filters
+                .exclude_modules
+                .push(args.next().unwrap_or_else(|| usage())),
+            "--filtered-edges" => {
+                options.collapse_filtered = match args.next().as_deref() {
+                    Some("drop") => false,
+                    Some("collapse") => true,
+                    _ => usage(),
+                }
+            }
+            "-h" | "--help" => usage(),
+            _ => options.positional.push(arg),
+        }
+    }
+    options
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(2)
+}
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("rcvis: {}", message);
+    process::exit(1)
+}
+
+/// A directory stands for the crate in it, see `crate_root`
+fn root_file(source_file_path: &str) -> PathBuf {
+    let path = Path::new(source_file_path);
+    match path.is_dir() {
+        true => crate_root(path).unwrap_or_else(|| {
+            fail(format!(
+                "{}: no src/lib.rs or src/main.rs",
+                source_file_path
+            ))
+        }),
+        false => path.to_path_buf(),
+    }
+}
+
+fn load(source_file_path: &str) -> Model {
+    load_model(&root_file(source_file_path))
+        .unwrap_or_else(|err| fail(format!("{}: {}", source_file_path, err)))
+}
+
+/// The parsed files behind `load`, for the commands that look past type definitions
+fn load_sources(source_file_path: &str) -> Vec<(PathBuf, File)> {
+    load_files(&root_file(source_file_path))
+        .unwrap_or_else(|err| fail(format!("{}: {}", source_file_path, err)))
+}
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-    let source_file_path = &args[1];
+    let mut args = env::args().skip(1).peekable();
+    match args.peek().map(String::as_str) {
+        Some("json-schema") => {
+            args.next();
+            json_schema_command(parse_options(args))
+        }
+        Some("er") => {
+            args.next();
+            er_command(parse_options(args))
+        }
+        Some("states") => {
+            args.next();
+            states_command(parse_options(args))
+        }
+        Some("modules") => {
+            args.next();
+            modules_command(parse_options(args))
+        }
+        Some("uses") => {
+            args.next();
+            uses_command(parse_options(args))
+        }
+        Some("cycles") => {
+            args.next();
+            cycles_command(parse_options(args))
+        }
+        Some("leaks") => {
+            args.next();
+            leaks_command(parse_options(args))
+        }
+        Some("check") => {
+            args.next();
+            check_command(parse_options(args))
+        }
+        Some("diff") => {
+            args.next();
+            diff_command(parse_options(args))
+        }
+        Some("breaking") => {
+            args.next();
+            breaking_command(parse_options(args))
+        }
+        Some("stats") => {
+            args.next();
+            stats_command(parse_options(args))
+        }
+        Some("feature-diff") => {
+            args.next();
+            feature_diff_command(parse_options(args))
+        }
+        _ => graph_command(parse_options(args)),
+    }
+}
+
+fn graph_command(options: Options) {
+    let [source_file_path] = options.positional.as_slice() else {
+        usage()
+    };
+    let mut model = apply_cfg(&load(source_file_path), &options.cfg);
+    if options.public_only {
+        model = public_api(&model);
+    }
+    if options.serde {
+        model = serde_view(&model);
+    }
+    let mut graph_options = options.graph.clone();
+    graph_options.modules = model.modules.clone();
+    if options.group_external {
+        graph_options.external_crates = external_crates(&model);
+    }
+    if !options.filters.is_empty() {
+        if options.collapse_filtered {
+            graph_options.placeholders = model
+                .elements
+                .iter()
+                .filter(|element| !options.filters.keeps(element))
+                .map(|element| element.name().to_string())
+                .collect();
+        }
+        model = filter_model(&model, &options.filters);
+    }
+    let mut structs = model.elements;
+    let mut graph = DiGraph::<String, Edge>::new();
+    let mut node_indices = create_nodes_for_graph(&structs, &mut graph);
+    add_edges_for_graph_with_options(&structs, &mut graph, &node_indices, &graph_options);
+    if let Some(type_name) = &options.focus {
+        let focus = Focus {
+            type_name: type_name.clone(),
+            depth: options.depth.unwrap_or(1),
+            direction: options.direction,
+        };
+        (structs, graph, node_indices) = focus_graph(&structs, &graph, &node_indices, &focus)
+            .unwrap_or_else(|| fail(format!("no type named {}", type_name)));
+    }
+    let mut export_options = options.export.clone();
+    if options.highlight_cycles {
+        export_options.highlighted_edges = find_cycles(&structs, &graph, &node_indices)
+            .iter()
+            .flat_map(|cycle| cycle.edge_indices())
+            .collect();
+    }
+
+    let export = match options.format.unwrap_or_default() {
+        Format::Dot => to_dot,
+        Format::Mermaid => to_mermaid,
+        Format::PlantUml => to_plantuml,
+        _ => fail("the type graph is available as --format dot, mermaid or plantuml"),
+    };
+    print!(
+        "{}",
+        export(&structs, &graph, &node_indices, &export_options)
+    );
+}
+
+fn feature_diff_command(options: Options) {
+    let [feature, source_file_path] = options.positional.as_slice() else {
+        usage()
+    };
+    let manifest_path = options
+        .manifest_path
+        .clone()
+        .or_else(|| find_manifest(Path::new(source_file_path)))
+        .unwrap_or_else(|| fail("Cargo.toml not found, pass --manifest-path"));
+    let features = CargoFeatures::read(&manifest_path).unwrap_or_else(|err| fail(err));
+
+    let mut model = load(source_file_path);
+    if options.public_only {
+        model = public_api(&model);
+    }
+    let diff = feature_diff(
+        &model,
+        &features,
+        feature,
+        !options.no_default_features,
+        &options.cfg,
+    )
+    .unwrap_or_else(|err| fail(format!("{}: {}", manifest_path.display(), err)));
+    print_diff(&diff, options.format.unwrap_or_default());
+}
+
+fn diff_command(options: Options) {
+    let [old_path, new_path] = options.positional.as_slice() else {
+        usage()
+    };
+    let prepare = |path: &str| {
+        let mut model = apply_cfg(&load(path), &options.cfg);
+        if options.public_only {
+            model = public_api(&model);
+        }
+        filter_model(&model, &options.filters)
+    };
+    print_diff(
+        &diff_models(&prepare(old_path), &prepare(new_path)),
+        options.format.unwrap_or_default(),
+    );
+}
+
+/// Exits with 1 when the changes need a major version, for release pipelines
+fn breaking_command(options: Options) {
+    let [old_path, new_path] = options.positional.as_slice() else {
+        usage()
+    };
+    let old = apply_cfg(&load(old_path), &options.cfg);
+    let new = apply_cfg(&load(new_path), &options.cfg);
+    let changes = api_changes(&old, &new);
+    for change in &changes {
+        println!("{}", change);
+    }
+    let bump = required_bump(&changes);
+    println!("required version bump: {}", bump);
+    if bump == Impact::Major {
+        process::exit(1);
+    }
+}
+
+fn print_diff(diff: &ModelDiff, format: Format) {
+    match format {
+        Format::Dot => print!("{}", diff_to_dot(diff)),
+        Format::Text => print!("{}", diff),
+        _ => fail("diffs are available as --format dot or text"),
+    }
+}
+
+fn json_schema_command(options: Options) {
+    let [root, source_file_path] = options.positional.as_slice() else {
+        usage()
+    };
+    let mut model = apply_cfg(&load(source_file_path), &options.cfg);
+    if options.public_only {
+        model = public_api(&model);
+    }
+    let schema = json_schema(&model, root)
+        .unwrap_or_else(|| fail(format!("no struct or enum named {}", root)));
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&schema).expect("schema serializes")
+    );
+}
+
+fn er_command(options: Options) {
+    let [source_file_path] = options.positional.as_slice() else {
+        usage()
+    };
+    let model = apply_cfg(&load(source_file_path), &options.cfg);
+    let diagram = er_diagram(&model);
+    match options.format.unwrap_or_default() {
+        Format::Dot => print!("{}", er_to_dot(&diagram)),
+        Format::Mermaid => print!("{}", er_to_mermaid(&diagram)),
+        _ => fail("the ER diagram is available as --format dot or mermaid"),
+    }
+}
+
+fn states_command(options: Options) {
+    let [enum_name, source_file_path] = options.positional.as_slice() else {
+        usage()
+    };
+    let model = apply_cfg(&load(source_file_path), &options.cfg);
+    let files: Vec<_> = apply_cfg_to_files(&load_sources(source_file_path), &options.cfg)
+        .into_iter()
+        .map(|(_, file)| file)
+        .collect();
+    let machine = state_machine(&model, &files, enum_name)
+        .unwrap_or_else(|| fail(format!("no enum named {}", enum_name)));
+    match options.format.unwrap_or_default() {
+        Format::Dot => print!("{}", state_machine_to_dot(&machine)),
+        Format::Mermaid => print!("{}", state_machine_to_mermaid(&machine)),
+        _ => fail("the state diagram is available as --format dot or mermaid"),
+    }
+}
+
+fn modules_command(options: Options) {
+    let [source_file_path] = options.positional.as_slice() else {
+        usage()
+    };
+    if options.format.unwrap_or_default() != Format::Dot {
+        fail("the module graph is only available as --format dot");
+    }
+    let mut model = apply_cfg(&load(source_file_path), &options.cfg);
+    if options.public_only {
+        model = public_api(&model);
+    }
+    model = filter_model(&model, &options.filters);
+    print!("{}", module_graph_to_dot(&module_graph(&model)));
+}
+
+fn cycles_command(options: Options) {
+    for cycle in cycles(&options) {
+        println!("{}", cycle);
+    }
+}
+
+/// Exits with 1 when there's a leak, for CI
+fn leaks_command(options: Options) {
+    let leaks = find_leaks(&cycles(&options));
+    for leak in &leaks {
+        println!("{}", leak);
+    }
+    if !leaks.is_empty() {
+        process::exit(1);
+    }
+}
+
+fn cycles(options: &Options) -> Vec<Cycle> {
+    let [source_file_path] = options.positional.as_slice() else {
+        usage()
+    };
+    let mut model = apply_cfg(&load(source_file_path), &options.cfg);
+    if options.public_only {
+        model = public_api(&model);
+    }
+    model = filter_model(&model, &options.filters);
+    let graph_options = GraphOptions {
+        modules: model.modules.clone(),
+        ..options.graph.clone()
+    };
+    let mut graph = DiGraph::<String, Edge>::new();
+    let node_indices = create_nodes_for_graph(&model.elements, &mut graph);
+    add_edges_for_graph_with_options(&model.elements, &mut graph, &node_indices, &graph_options);
+    find_cycles(&model.elements, &graph, &node_indices)
+}
 
-    let source_code = fs::read_to_string(source_file_path).unwrap();
+/// Exits with 1 when a rule is broken, for CI
+fn check_command(options: Options) {
+    let ([source_file_path], Some(rules_path)) = (options.positional.as_slice(), &options.rules)
+    else {
+        usage()
+    };
+    let rules = Rules::read(rules_path)
+        .unwrap_or_else(|err| fail(format!("{}: {}", rules_path.display(), err)));
+    let mut model = apply_cfg(&load(source_file_path), &options.cfg);
+    model = filter_model(&model, &options.filters);
+    let graph_options = GraphOptions {
+        modules: model.modules.clone(),
+        ..options.graph.clone()
+    };
+    let mut graph = DiGraph::<String, Edge>::new();
+    let node_indices = create_nodes_for_graph(&model.elements, &mut graph);
+    add_edges_for_graph_with_options(&model.elements, &mut graph, &node_indices, &graph_options);
 
-    let parsed = parse_rust_code(&source_code);
-    let structs = from_item_to_structs(filter_structs(parsed));
-    let mut graph = DiGraph::<String, ()>::new();
-    let node_indices = create_nodes_for_graph(&structs, &mut graph);
-    add_edges_for_graph(&structs, &mut graph, &node_indices);
+    let mut violations = check_rules(&model.elements, &graph, &node_indices, &rules);
+    let files = load_sources(source_file_path);
+    locate_violations(&mut violations, &files);
+    for violation in &violations {
+        println!("{}", violation);
+    }
+    if !violations.is_empty() {
+        process::exit(1);
+    }
+}
+
+fn stats_command(options: Options) {
+    let [source_file_path] = options.positional.as_slice() else {
+        usage()
+    };
+    let mut model = apply_cfg(&load(source_file_path), &options.cfg);
+    if options.public_only {
+        model = public_api(&model);
+    }
+    let stats = stats(&filter_model(&model, &options.filters));
+    match options.format.unwrap_or(Format::Text) {
+        Format::Text => print!("{}", stats_to_table(&stats)),
+        Format::Csv => print!("{}", stats_to_csv(&stats)),
+        Format::Json => println!(
+            "{}",
+            serde_json::to_string_pretty(&stats_to_json(&stats)).unwrap()
+        ),
+        _ => fail("stats are available as --format text, csv or json"),
+    }
+}
 
-    println!("{:?}", Dot::with_config(&graph, &[Config::EdgeNoLabel]));
+fn uses_command(options: Options) {
+    let [type_name, source_file_path] = options.positional.as_slice() else {
+        usage()
+    };
+    let files = apply_cfg_to_files(&load_sources(source_file_path), &options.cfg);
+    for reference in find_references(&files, type_name) {
+        println!("{}", reference);
+    }
 }
@@ -0,0 +1,501 @@
+use crate::funcs::out_of_line_modules;
+use crate::impls::tokens_to_string;
+use crate::structures::*;
+use std::mem;
+use std::path::{Path, PathBuf};
+use syn::punctuated::Punctuated;
+use syn::{Attribute, Expr, ExprLit, Fields, File, ImplItem, Item, Lit, Meta, Token, TraitItem};
+
+/// Build configuration that `#[cfg]` predicates are evaluated against.
+/// Whatever is left as `None` is unknown and does not exclude anything, so the default
+/// configuration keeps every item, as if no evaluation happened
+#[derive(Debug, Clone, Default)]
+pub struct CfgConfig {
+    /// Enabled Cargo features (`--features a,b`)
+    pub features: Option<Vec<String>>,
+    /// Set names (`unix`, `debug_assertions`) and key-value options (`target_os = "linux"`,
+    /// stored as `target_os=linux`), from `--cfg`
+    pub cfgs: Option<Vec<String>>,
+    /// `cfg(test)`, `Some(false)` with `--no-test`
+    pub test: Option<bool>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum CfgPredicate {
+    Name(String),
+    KeyValue(String, String),
+    All(Vec<CfgPredicate>),
+    Any(Vec<CfgPredicate>),
+    Not(Box<CfgPredicate>),
+}
+
+impl TryFrom<&Meta> for CfgPredicate {
+    type Error = syn::Error;
+    fn try_from(meta: &Meta) -> Result<Self, Self::Error> {
+        match meta {
+            Meta::Path(path) => Ok(CfgPredicate::Name(tokens_to_string(path))),
+            Meta::NameValue(name_value) => match &name_value.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(value),
+                    ..
+                }) => Ok(CfgPredicate::KeyValue(
+                    tokens_to_string(&name_value.path),
+                    value.value(),
+                )),
+                value => Err(syn::Error::new_spanned(value, "expected a string literal")),
+            },
+            Meta::List(list) => {
+                let nested =
+                    list.parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)?;
+                let mut predicates = nested
+                    .iter()
+                    .map(CfgPredicate::try_from)
+                    .collect::<Result<Vec<_>, _>>()?;
+                if list.path.is_ident("all") {
+                    Ok(CfgPredicate::All(predicates))
+                } else if list.path.is_ident("any") {
+                    Ok(CfgPredicate::Any(predicates))
+                } else if list.path.is_ident("not") && predicates.len() == 1 {
+                    Ok(CfgPredicate::Not(Box::new(predicates.remove(0))))
+                } else {
+                    Err(syn::Error::new_spanned(list, "unknown cfg predicate"))
+                }
+            }
+        }
+    }
+}
+
+impl CfgPredicate {
+    /// Kleene logic: `None` when the configuration does not know the answer
+    pub fn evaluate(&self, config: &CfgConfig) -> Option<bool> {
+        match self {
+            CfgPredicate::Name(name) if name == "test" => config.test,
+            CfgPredicate::Name(name) => config.cfgs.as_ref().map(|cfgs| cfgs.contains(name)),
+            CfgPredicate::KeyValue(key, value) if key == "feature" => config
+                .features
+                .as_ref()
+                .map(|features| features.contains(value)),
+            CfgPredicate::KeyValue(key, value) => config
+                .cfgs
+                .as_ref()
+                .map(|cfgs| cfgs.contains(&format!("{}={}", key, value))),
+            CfgPredicate::All(predicates) => {
+                let results: Vec<Option<bool>> =
+                    predicates.iter().map(|p| p.evaluate(config)).collect();
+                if results.contains(&Some(false)) {
+                    Some(false)
+                } else if results.contains(&None) {
+                    None
+                } else {
+                    Some(true)
+                }
+            }
+            CfgPredicate::Any(predicates) => {
+                let results: Vec<Option<bool>> =
+                    predicates.iter().map(|p| p.evaluate(config)).collect();
+                if results.contains(&Some(true)) {
+                    Some(true)
+                } else if results.contains(&None) {
+                    None
+                } else {
+                    Some(false)
+                }
+            }
+            CfgPredicate::Not(predicate) => predicate.evaluate(config).map(|result| !result),
+        }
+    }
+}
+
+/// `false` only when one of the `cfg(...)` attributes is known to be false
+pub fn is_enabled(attrs: &Attributes, config: &CfgConfig) -> bool {
+    attrs
+        .other
+        .iter()
+        .filter_map(|attr| syn::parse_str::<Meta>(attr).ok())
+        .filter_map(|meta| match meta {
+            Meta::List(list) if list.path.is_ident("cfg") => list.parse_args::<Meta>().ok(),
+            _ => None,
+        })
+        .filter_map(|predicate| CfgPredicate::try_from(&predicate).ok())
+        .all(|predicate| predicate.evaluate(config) != Some(false))
+}
+
+/// Replaces `cfg_attr(predicate, attrs...)` by the attributes when the predicate is not known
+/// to be false, so `#[cfg_attr(feature = "serde", derive(Serialize))]` becomes a derive
+pub fn expand_cfg_attr(attrs: &Attributes, config: &CfgConfig) -> Attributes {
+    let mut expanded = Attributes {
+        derives: attrs.derives.clone(),
+        other: Vec::new(),
+        docs: attrs.docs.clone(),
+    };
+    for attr in &attrs.other {
+        let nested = match syn::parse_str::<Meta>(attr) {
+            Ok(Meta::List(list)) if list.path.is_ident("cfg_attr") => list
+                .parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+                .ok(),
+            _ => None,
+        };
+        let Some(nested) = nested else {
+            expanded.other.push(attr.clone());
+            continue;
+        };
+        let mut nested = nested.into_iter();
+        let enabled = nested
+            .next()
+            .and_then(|predicate| CfgPredicate::try_from(&predicate).ok())
+            .map(|predicate| predicate.evaluate(config) != Some(false))
+            .unwrap_or(true);
+        if !enabled {
+            continue;
+        }
+        for meta in nested {
+            let text = tokens_to_string(&meta);
+            match &meta {
+                Meta::List(list) if list.path.is_ident("derive") => {
+                    if let Ok(paths) =
+                        list.parse_args_with(Punctuated::<syn::Path, Token![,]>::parse_terminated)
+                    {
+                        expanded.derives.extend(paths.iter().map(tokens_to_string));
+                    }
+                }
+                _ => expanded.other.push(text),
+            }
+        }
+    }
+    expanded
+}
+
+/// Drops modules, elements, fields and variants whose `#[cfg]` is false for the configuration
+/// and expands `cfg_attr` everywhere. Elements of a dropped module are dropped with it
+pub fn apply_cfg(model: &Model, config: &CfgConfig) -> Model {
+    let disabled_modules: Vec<&Vec<String>> = model
+        .modules
+        .iter()
+        .filter(|module| !is_enabled(&module.attrs, config))
+        .map(|module| &module.path)
+        .collect();
+    let in_disabled_module = |path: &[String]| {
+        disabled_modules
+            .iter()
+            .any(|disabled| path.starts_with(disabled))
+    };
+
+    let modules = model
+        .modules
+        .iter()
+        .filter(|module| !in_disabled_module(&module.path))
+        .cloned()
+        .map(|mut module| {
+            module.attrs = expand_cfg_attr(&module.attrs, config);
+            module
+        })
+        .collect();
+    let elements = model
+        .elements
+        .iter()
+        .filter(|element| {
+            !in_disabled_module(element.module()) && is_enabled(element.attrs(), config)
+        })
+        .cloned()
+        .map(|element| apply_cfg_to_element(element, config))
+        .collect();
+
+    Model { modules, elements }
+}
+
+fn apply_cfg_to_element(mut element: Element, config: &CfgConfig) -> Element {
+    match &mut element {
+        Element::Struct(struct_element) => {
+            struct_element.attrs = expand_cfg_attr(&struct_element.attrs, config);
+            apply_cfg_to_fields(&mut struct_element.fields, config);
+        }
+        Element::Enum(enum_element) => {
+            enum_element.attrs = expand_cfg_attr(&enum_element.attrs, config);
+            enum_element
+                .variants
+                .retain(|variant| is_enabled(&variant.attrs, config));
+            for variant in &mut enum_element.variants {
+                variant.attrs = expand_cfg_attr(&variant.attrs, config);
+                apply_cfg_to_fields(&mut variant.fields, config);
+            }
+        }
+        Element::Trait(trait_element) => {
+            trait_element.attrs = expand_cfg_attr(&trait_element.attrs, config);
+        }
+    }
+    element
+}
+
+fn apply_cfg_to_fields(fields: &mut Vec<Field>, config: &CfgConfig) {
+    fields.retain(|field| is_enabled(&field.attrs, config));
+    for field in fields {
+        field.attrs = expand_cfg_attr(&field.attrs, config);
+    }
+}
+
+/// `apply_cfg` for the files of `load_files`: items, impl and trait items, fields and variants
+/// whose `#[cfg]` is false are dropped, and so are the files of dropped `mod a;` declarations.
+/// Function bodies are kept as they are
+pub fn apply_cfg_to_files(files: &[(PathBuf, File)], config: &CfgConfig) -> Vec<(PathBuf, File)> {
+    let mut kept = Vec::new();
+    if let Some((root, _)) = files.first() {
+        let dir = root.parent().unwrap_or(Path::new(""));
+        keep_file(root, dir, files, config, &mut kept);
+    }
+    kept
+}
+
+fn keep_file(
+    path: &Path,
+    dir: &Path,
+    files: &[(PathBuf, File)],
+    config: &CfgConfig,
+    kept: &mut Vec<(PathBuf, File)>,
+) {
+    let Some((_, file)) = files.iter().find(|(file_path, _)| file_path == path) else {
+        return;
+    };
+    let mut file = file.clone();
+    file.items = apply_cfg_to_items(mem::take(&mut file.items), config);
+    let mut module_files = Vec::new();
+    out_of_line_modules(&file.items, dir, &mut module_files);
+    kept.push((path.to_path_buf(), file));
+    for (file_path, child_dir) in module_files {
+        keep_file(&file_path, &child_dir, files, config, kept);
+    }
+}
+
+fn apply_cfg_to_items(items: Vec<Item>, config: &CfgConfig) -> Vec<Item> {
+    let mut kept = Vec::new();
+    for mut item in items {
+        let attrs = match &item {
+            Item::Const(item) => &item.attrs,
+            Item::Enum(item) => &item.attrs,
+            Item::ExternCrate(item) => &item.attrs,
+            Item::Fn(item) => &item.attrs,
+            Item::ForeignMod(item) => &item.attrs,
+            Item::Impl(item) => &item.attrs,
+            Item::Macro(item) => &item.attrs,
+            Item::Mod(item) => &item.attrs,
+            Item::Static(item) => &item.attrs,
+            Item::Struct(item) => &item.attrs,
+            Item::Trait(item) => &item.attrs,
+            Item::TraitAlias(item) => &item.attrs,
+            Item::Type(item) => &item.attrs,
+            Item::Union(item) => &item.attrs,
+            Item::Use(item) => &item.attrs,
+            _ => {
+                kept.push(item);
+                continue;
+            }
+        };
+        if !syn_enabled(attrs, config) {
+            continue;
+        }
+        match &mut item {
+            Item::Struct(item_struct) => apply_cfg_to_syn_fields(&mut item_struct.fields, config),
+            Item::Enum(item_enum) => {
+                item_enum.variants = mem::take(&mut item_enum.variants)
+                    .into_iter()
+                    .filter(|variant| syn_enabled(&variant.attrs, config))
+                    .collect();
+                for variant in &mut item_enum.variants {
+                    apply_cfg_to_syn_fields(&mut variant.fields, config);
+                }
+            }
+            Item::Mod(item_mod) => {
+                if let Some((_, items)) = &mut item_mod.content {
+                    *items = apply_cfg_to_items(mem::take(items), config);
+                }
+            }
+            Item::Impl(item_impl) => item_impl.items.retain(|impl_item| match impl_item {
+                ImplItem::Const(item) => syn_enabled(&item.attrs, config),
+                ImplItem::Fn(item) => syn_enabled(&item.attrs, config),
+                ImplItem::Type(item) => syn_enabled(&item.attrs, config),
+                ImplItem::Macro(item) => syn_enabled(&item.attrs, config),
+                _ => true,
+            }),
+            Item::Trait(item_trait) => item_trait.items.retain(|trait_item| match trait_item {
+                TraitItem::Const(item) => syn_enabled(&item.attrs, config),
+                TraitItem::Fn(item) => syn_enabled(&item.attrs, config),
+                TraitItem::Type(item) => syn_enabled(&item.attrs, config),
+                TraitItem::Macro(item) => syn_enabled(&item.attrs, config),
+                _ => true,
+            }),
+            _ => {}
+        }
+        kept.push(item);
+    }
+    kept
+}
+
+fn apply_cfg_to_syn_fields(fields: &mut Fields, config: &CfgConfig) {
+    let fields = match fields {
+        Fields::Named(named) => &mut named.named,
+        Fields::Unnamed(unnamed) => &mut unnamed.unnamed,
+        Fields::Unit => return,
+    };
+    *fields = mem::take(fields)
+        .into_iter()
+        .filter(|field| syn_enabled(&field.attrs, config))
+        .collect();
+}
+
+fn syn_enabled(attrs: &[Attribute], config: &CfgConfig) -> bool {
+    is_enabled(&Attributes::from(attrs.to_vec()), config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::funcs::*;
+    use crate::references::find_references;
+
+    const TEST_CODE: &str = r#"
+        #[cfg_attr(feature = "serde", derive(Serialize), serde(rename_all = "camelCase"))]
+        pub struct Config {
+            pub name: String,
+            #[cfg(feature = "async")]
+            pub runtime: Runtime,
+            #[cfg(all(unix, not(target_os = "macos")))]
+            pub epoll: Epoll,
+        }
+
+        #[cfg(any(feature = "async", test))]
+        pub struct Runtime {}
+
+        pub enum Backend {
+            #[cfg(unix)]
+            Epoll(Epoll),
+            #[cfg(windows)]
+            Iocp,
+        }
+
+        pub struct Epoll {}
+
+        #[cfg(test)]
+        mod tests {
+            pub struct Fixture {}
+        }
+    "#;
+
+    fn names(model: &Model) -> Vec<&str> {
+        model.elements.iter().map(|e| e.name()).collect()
+    }
+
+    #[test]
+    fn test_default_config_keeps_everything() {
+        let model = collect_model(parse_rust_code(TEST_CODE));
+        let evaluated = apply_cfg(&model, &CfgConfig::default());
+
+        assert_eq!(
+            names(&evaluated),
+            vec!["Config", "Runtime", "Backend", "Epoll", "Fixture"]
+        );
+        assert_eq!(evaluated.elements[0].attrs().derives, vec!["Serialize"]);
+    }
+
+    #[test]
+    fn test_apply_cfg() {
+        let model = collect_model(parse_rust_code(TEST_CODE));
+        let config = CfgConfig {
+            features: Some(vec![]),
+            cfgs: Some(vec!["unix".to_string(), "target_os=linux".to_string()]),
+            test: Some(false),
+        };
+        let evaluated = apply_cfg(&model, &config);
+
+        assert_eq!(names(&evaluated), vec!["Config", "Backend", "Epoll"]);
+        assert_eq!(evaluated.modules.len(), 1);
+        match &evaluated.elements[0] {
+            Element::Struct(config_struct) => {
+                let fields: Vec<String> = config_struct
+                    .fields
+                    .iter()
+                    .map(|field| field.label(0))
+                    .collect();
+                assert_eq!(fields, vec!["name", "epoll"]);
+                assert!(config_struct.attrs.derives.is_empty());
+                assert!(config_struct.attrs.other.is_empty());
+            }
+            _ => panic!("Expected Element::Struct"),
+        }
+        match &evaluated.elements[1] {
+            Element::Enum(backend) => {
+                assert_eq!(backend.variants.len(), 1);
+                assert_eq!(backend.variants[0].name, "Epoll");
+            }
+            _ => panic!("Expected Element::Enum"),
+        }
+    }
+
+    #[test]
+    fn test_apply_cfg_to_files() {
+        let code = r#"
+            pub struct Config {
+                #[cfg(feature = "async")]
+                pub runtime: Runtime,
+                pub backend: Backend,
+            }
+
+            pub enum Backend {
+                #[cfg(unix)]
+                Epoll(Runtime),
+                Iocp,
+            }
+
+            impl Backend {
+                #[cfg(feature = "async")]
+                fn start(&self) -> Runtime {
+                    Runtime {}
+                }
+            }
+
+            #[cfg(test)]
+            mod tests {
+                pub struct Fixture {
+                    pub runtime: Runtime,
+                }
+            }
+        "#;
+        let files = vec![(PathBuf::from("lib.rs"), parse_rust_code(code))];
+        let config = CfgConfig {
+            features: Some(vec![]),
+            cfgs: Some(vec!["unix".to_string()]),
+            test: Some(false),
+        };
+        let evaluated = apply_cfg_to_files(&files, &config);
+
+        let owners: Vec<String> = find_references(&evaluated, "Runtime")
+            .into_iter()
+            .map(|reference| format!("{}.{}", reference.owner, reference.member))
+            .collect();
+        assert_eq!(owners, vec!["Backend.Epoll.0"]);
+        let file = &evaluated[0].1;
+        assert_eq!(file.items.len(), 3);
+        match &file.items[2] {
+            Item::Impl(item_impl) => assert!(item_impl.items.is_empty()),
+            _ => panic!("Expected Item::Impl"),
+        }
+        let everything = apply_cfg_to_files(&files, &CfgConfig::default());
+        assert_eq!(find_references(&everything, "Runtime").len(), 3);
+    }
+
+    #[test]
+    fn test_evaluate_unknown() {
+        let predicate: Meta = syn::parse_str(r#"any(feature = "a", unix)"#).unwrap();
+        let predicate = CfgPredicate::try_from(&predicate).unwrap();
+        let features_only = CfgConfig {
+            features: Some(vec!["b".to_string()]),
+            ..CfgConfig::default()
+        };
+
+        assert_eq!(predicate.evaluate(&CfgConfig::default()), None);
+        assert_eq!(predicate.evaluate(&features_only), None);
+        let not_unix = CfgConfig {
+            cfgs: Some(vec![]),
+            ..features_only
+        };
+        assert_eq!(predicate.evaluate(&not_unix), Some(false));
+    }
+}
@@ -0,0 +1,431 @@
+use crate::impls::wrapper_path;
+use crate::structures::*;
+use petgraph::algo::tarjan_scc;
+use petgraph::graph::{DiGraph, EdgeIndex, NodeIndex};
+use petgraph::visit::EdgeRef;
+use std::collections::VecDeque;
+use std::fmt;
+
+/// How the types of a cycle hold each other
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CycleKind {
+    /// Through `Box`, `Vec` and other owning wrappers: the values form trees and are dropped
+    Owned,
+    /// A loop goes only through `Rc` or `Arc` and not `Weak`: values can point back to each
+    /// other and never be dropped
+    Shared,
+    /// Every loop goes through `Weak`, which breaks them
+    Weak,
+}
+
+/// Field edge inside a cycle
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CycleEdge {
+    pub edge: EdgeIndex,
+    pub from: String,
+    pub to: String,
+    /// Edge label: `field`, `.0`, `Variant.field`
+    pub field: String,
+    /// Wrappers around `to` in the field type, outermost first, see `Type::wrappers_of`
+    pub wrappers: Vec<String>,
+}
+
+/// Strongly connected component of the field edges: a recursive type (`next: Option<Box<Node>>`)
+/// or mutually recursive types
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Cycle {
+    /// In node order
+    pub types: Vec<String>,
+    pub edges: Vec<CycleEdge>,
+    pub kind: CycleKind,
+}
+
+/// Every cycle of field edges in the graph built by `create_nodes_for_graph` and
+/// `add_edges_for_graph`, in node order. Other edge kinds (bindings, associated types) are
+/// left out
+pub fn find_cycles(
+    elements: &[Element],
+    graph: &DiGraph<String, Edge>,
+    node_indices: &[NodeIndex],
+) -> Vec<Cycle> {
+    // Same nodes, so the same indices, with only the field edges
+    let fields = graph.filter_map(
+        |_, _| Some(()),
+        |edge, weight| (weight.kind == EdgeKind::Field).then_some(edge),
+    );
+    let element_of = |node: NodeIndex| {
+        node_indices
+            .iter()
+            .position(|&i| i == node)
+            .map(|position| &elements[position])
+    };
+    let name_of = |node: NodeIndex| match element_of(node) {
+        Some(element) => element.name().to_string(),
+        None => graph[node].clone(),
+    };
+
+    let mut cycles: Vec<(NodeIndex, Cycle)> = Vec::new();
+    for mut component in tarjan_scc(&fields) {
+        component.sort();
+        let recursive =
+            component.len() > 1 || fields.find_edge(component[0], component[0]).is_some();
+        if !recursive {
+            continue;
+        }
+        let mut edges: Vec<CycleEdge> = Vec::new();
+        for edge in fields.edge_references() {
+            if !component.contains(&edge.source()) || !component.contains(&edge.target()) {
+                continue;
+            }
+            let field = graph[*edge.weight()].field.clone().unwrap_or_default();
+            let to = name_of(edge.target());
+            // A field mentioning the target twice (`(Rc<Node>, Weak<Node>)`) has one edge per
+            // occurrence, in the order `wrappers_of` finds them
+            let occurrence = edges
+                .iter()
+                .filter(|other| {
+                    other.from == name_of(edge.source()) && other.to == to && other.field == field
+                })
+                .count();
+            let occurrences = element_of(edge.source())
+                .and_then(|element| element.field(&field))
+                .map(|field| field.ty.wrappers_of(&to))
+                .unwrap_or_default();
+            let wrappers = occurrences
+                .get(occurrence)
+                .or(occurrences.first())
+                .cloned()
+                .unwrap_or_default();
+            edges.push(CycleEdge {
+                edge: *edge.weight(),
+                from: name_of(edge.source()),
+                to,
+                field,
+                wrappers,
+            });
+        }
+        let types: Vec<String> = component.iter().map(|&node| name_of(node)).collect();
+        let kind = cycle_kind(&types, &edges);
+        cycles.push((component[0], Cycle { types, edges, kind }));
+    }
+    cycles.sort_by_key(|(first, _)| *first);
+    cycles.into_iter().map(|(_, cycle)| cycle).collect()
+}
+
+fn through(edge: &CycleEdge, names: &[&str]) -> bool {
+    edge.wrappers
+        .iter()
+        .any(|wrapper| names.contains(&wrapper.as_str()))
+}
+
+/// Strong edges hold through `Rc` or `Arc` and not through `Weak`
+fn is_strong(edge: &CycleEdge) -> bool {
+    through(edge, &["Rc", "Arc"]) && !through(edge, &["Weak"])
+}
+
+/// Graph of `types` with the `edges` kept by `keep`, weighted by their position in `edges`
+fn loop_graph(
+    types: &[String],
+    edges: &[CycleEdge],
+    keep: impl Fn(&CycleEdge) -> bool,
+) -> DiGraph<(), usize> {
+    let mut graph = DiGraph::<(), usize>::new();
+    let nodes: Vec<NodeIndex> = types.iter().map(|_| graph.add_node(())).collect();
+    let node_of = |name: &str| types.iter().position(|t| t == name).map(|i| nodes[i]);
+    for (position, edge) in edges.iter().enumerate() {
+        if !keep(edge) {
+            continue;
+        }
+        if let (Some(from), Some(to)) = (node_of(&edge.from), node_of(&edge.to)) {
+            graph.add_edge(from, to, position);
+        }
+    }
+    graph
+}
+
+fn has_loop(graph: &DiGraph<(), usize>) -> bool {
+    tarjan_scc(graph).iter().any(|component| {
+        component.len() > 1 || graph.find_edge(component[0], component[0]).is_some()
+    })
+}
+
+/// Shared when a loop holds only through strong edges, Weak when every loop goes through a
+/// `Weak` edge
+fn cycle_kind(types: &[String], edges: &[CycleEdge]) -> CycleKind {
+    if has_loop(&loop_graph(types, edges, is_strong)) {
+        CycleKind::Shared
+    } else if !has_loop(&loop_graph(types, edges, |edge| !through(edge, &["Weak"]))) {
+        CycleKind::Weak
+    } else {
+        CycleKind::Owned
+    }
+}
+
+/// Loop of strong `Rc`/`Arc` edges without a `Weak` one: values on it keep each other alive
+/// and are never dropped
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Leak {
+    /// Edges of the loop in order, the last one closes it back to the first type
+    pub edges: Vec<CycleEdge>,
+}
+
+/// One shortest loop for every group of types in `cycles` connected only by strong `Rc`/`Arc`
+/// edges. A `Vec<Rc<T>>` field is strong, a `Weak<T>` or `Rc<Weak<T>>` field is not, so a
+/// `Weak` field only breaks the loops going through it
+pub fn find_leaks(cycles: &[Cycle]) -> Vec<Leak> {
+    let mut leaks = Vec::new();
+    for cycle in cycles {
+        let strong = loop_graph(&cycle.types, &cycle.edges, is_strong);
+        for component in tarjan_scc(&strong) {
+            let start = *component.iter().min().unwrap();
+            if let Some(path) = shortest_loop(&strong, start) {
+                let edges = path.iter().map(|&i| cycle.edges[i].clone()).collect();
+                leaks.push(Leak { edges });
+            }
+        }
+    }
+    leaks.sort_by(|a, b| a.edges[0].edge.cmp(&b.edges[0].edge));
+    leaks
+}
+
+/// Weights of the edges of the shortest loop from `start` back to itself, by breadth-first search
+fn shortest_loop(graph: &DiGraph<(), usize>, start: NodeIndex) -> Option<Vec<usize>> {
+    let mut reached_by: Vec<Option<(NodeIndex, usize)>> = vec![None; graph.node_count()];
+    let mut queue = VecDeque::from([start]);
+    while let Some(node) = queue.pop_front() {
+        for edge in graph.edges(node) {
+            let target = edge.target();
+            if target == start {
+                let mut path = vec![*edge.weight()];
+                let mut current = node;
+                while current != start {
+                    let (previous, weight) = reached_by[current.index()]?;
+                    path.push(weight);
+                    current = previous;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            if reached_by[target.index()].is_none() {
+                reached_by[target.index()] = Some((node, *edge.weight()));
+                queue.push_back(target);
+            }
+        }
+    }
+    None
+}
+
+impl Leak {
+    /// Last edge, the field that closes the loop
+    pub fn closing_edge(&self) -> &CycleEdge {
+        self.edges.last().unwrap()
+    }
+}
+
+impl Cycle {
+    /// A single type containing itself, like `Box<Self>`
+    pub fn is_direct(&self) -> bool {
+        self.types.len() == 1
+    }
+
+    pub fn edge_indices(&self) -> Vec<EdgeIndex> {
+        self.edges.iter().map(|edge| edge.edge).collect()
+    }
+}
+
+impl CycleEdge {
+    /// `Option<Box<Node>>`, the field type with only the wrappers leading to `to`
+    pub fn wrapper_path(&self) -> String {
+        wrapper_path(&self.wrappers, &self.to)
+    }
+}
+
+impl fmt::Display for CycleKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CycleKind::Owned => write!(f, "owned"),
+            CycleKind::Shared => write!(f, "only through Rc/Arc, may leak"),
+            CycleKind::Weak => write!(f, "broken by Weak"),
+        }
+    }
+}
+
+/// `mutually recursive types Parent, Child (only through Rc/Arc, may leak)` followed by one
+/// indented line per edge: `Parent.children -> Child: Vec<Rc<Child>>`
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.is_direct() {
+            true => write!(f, "recursive type {}", self.types[0])?,
+            false => write!(f, "mutually recursive types {}", self.types.join(", "))?,
+        }
+        write!(f, " ({})", self.kind)?;
+        for edge in &self.edges {
+            write!(
+                f,
+                "\n    {}.{} -> {}: {}",
+                edge.from,
+                edge.field.trim_start_matches('.'),
+                edge.to,
+                edge.wrapper_path()
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// `warning: Rc/Arc cycle Parent.children -> Child.parent -> Parent is never dropped, closed by
+/// Child.parent: Rc<RefCell<Parent>>`
+impl fmt::Display for Leak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path: Vec<String> = self
+            .edges
+            .iter()
+            .map(|edge| format!("{}.{}", edge.from, edge.field.trim_start_matches('.')))
+            .collect();
+        let closing = self.closing_edge();
+        write!(
+            f,
+            "warning: Rc/Arc cycle {} -> {} is never dropped, closed by {}.{}: {}",
+            path.join(" -> "),
+            closing.to,
+            closing.from,
+            closing.field.trim_start_matches('.'),
+            closing.wrapper_path()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::funcs::*;
+
+    const TEST_CODE: &str = "
+        pub struct Node {
+            pub value: u64,
+            pub next: Option<Box<Node>>,
+        }
+
+        pub struct Parent {
+            pub children: Vec<Rc<RefCell<Child>>>,
+        }
+
+        pub struct Child {
+            pub parent: Rc<RefCell<Parent>>,
+        }
+
+        pub struct Tree {
+            pub root: Rc<TreeNode>,
+        }
+
+        pub enum TreeNode {
+            Leaf,
+            Branch { children: Vec<Rc<TreeNode>>, parent: Weak<TreeNode> },
+        }
+
+        pub struct Subject {
+            pub observers: Vec<Rc<Observer>>,
+        }
+
+        pub struct Observer {
+            pub subject: Weak<Subject>,
+        }
+    ";
+
+    fn cycles() -> (Vec<Cycle>, DiGraph<String, Edge>) {
+        let elements = from_item_to_structs(filter_structs(parse_rust_code(TEST_CODE)));
+        let mut graph = DiGraph::<String, Edge>::new();
+        let node_indices = create_nodes_for_graph(&elements, &mut graph);
+        add_edges_for_graph(&elements, &mut graph, &node_indices);
+        (find_cycles(&elements, &graph, &node_indices), graph)
+    }
+
+    #[test]
+    fn test_find_cycles() {
+        let (cycles, graph) = cycles();
+        let found: Vec<(Vec<&str>, CycleKind, usize)> = cycles
+            .iter()
+            .map(|cycle| {
+                let types = cycle.types.iter().map(String::as_str).collect();
+                (types, cycle.kind, cycle.edges.len())
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (vec!["Node"], CycleKind::Owned, 1),
+                (vec!["Parent", "Child"], CycleKind::Shared, 2),
+                (vec!["TreeNode"], CycleKind::Shared, 2),
+                (vec!["Subject", "Observer"], CycleKind::Weak, 2),
+            ]
+        );
+        assert!(cycles[0].is_direct());
+        assert!(!cycles[1].is_direct());
+        for edge in cycles.iter().flat_map(Cycle::edge_indices) {
+            assert_eq!(graph[edge].kind, EdgeKind::Field);
+        }
+    }
+
+    #[test]
+    fn test_cycle_display() {
+        let (cycles, _) = cycles();
+        assert_eq!(
+            cycles[0].to_string(),
+            "recursive type Node (owned)\n    Node.next -> Node: Option<Box<Node>>"
+        );
+        assert_eq!(
+            cycles[1].to_string(),
+            "mutually recursive types Parent, Child (only through Rc/Arc, may leak)\n    \
+             Parent.children -> Child: Vec<Rc<RefCell<Child>>>\n    \
+             Child.parent -> Parent: Rc<RefCell<Parent>>"
+        );
+        assert_eq!(cycles[2].edges[1].wrapper_path(), "Weak<TreeNode>");
+    }
+
+    #[test]
+    fn test_find_leaks() {
+        let code = "
+        pub struct Parent {
+            pub children: Vec<Rc<RefCell<Child>>>,
+            pub first: Weak<Child>,
+            pub log: Box<Log>,
+        }
+
+        pub struct Child {
+            pub parent: Rc<RefCell<Parent>>,
+        }
+
+        pub struct Log {
+            pub owner: Box<Parent>,
+            pub shared: Option<Arc<Log>>,
+        }
+
+        pub struct Tree {
+            pub children: Vec<Rc<Tree>>,
+            pub parent: Weak<Tree>,
+        }
+        ";
+        let elements = from_item_to_structs(filter_structs(parse_rust_code(code)));
+        let mut graph = DiGraph::<String, Edge>::new();
+        let node_indices = create_nodes_for_graph(&elements, &mut graph);
+        add_edges_for_graph(&elements, &mut graph, &node_indices);
+        let cycles = find_cycles(&elements, &graph, &node_indices);
+        assert_eq!(cycles.len(), 2);
+
+        let leaks = find_leaks(&cycles);
+        let paths: Vec<Vec<&str>> = leaks
+            .iter()
+            .map(|leak| leak.edges.iter().map(|edge| edge.field.as_str()).collect())
+            .collect();
+        assert_eq!(
+            paths,
+            vec![vec!["children", "parent"], vec!["shared"], vec!["children"]]
+        );
+        assert_eq!(
+            leaks[0].to_string(),
+            "warning: Rc/Arc cycle Parent.children -> Child.parent -> Parent is never dropped, \
+             closed by Child.parent: Rc<RefCell<Parent>>"
+        );
+        assert_eq!(leaks[1].closing_edge().wrapper_path(), "Option<Arc<Log>>");
+    }
+}
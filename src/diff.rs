@@ -0,0 +1,525 @@
+use crate::export::{escape_html, members};
+use crate::funcs::*;
+use crate::structures::*;
+use petgraph::graph::DiGraph;
+use petgraph::visit::EdgeRef;
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Change {
+    Added,
+    Removed,
+    Changed,
+    Unchanged,
+}
+
+/// Structural difference between two models, elements are matched by module path and name
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ModelDiff {
+    pub elements: Vec<ElementDiff>,
+    pub edges: Vec<EdgeDiff>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ElementDiff {
+    pub name: String,
+    pub module: Vec<String>,
+    /// Label of the new element, or of the old one when it was removed
+    pub label: String,
+    pub change: Change,
+    pub members: Vec<MemberDiff>,
+}
+
+/// Field (matched by name or position), variant, associated type or derive
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct MemberDiff {
+    pub name: String,
+    pub change: Change,
+    /// Rendered as by `export::members`: `+ name: Type`, `Variant(A, B)`
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+/// Endpoints are `module::Name` paths of elements (`Name` at the crate root), or the label of a
+/// node that is not an element
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EdgeDiff {
+    pub source: String,
+    pub target: String,
+    pub field: Option<String>,
+    pub kind: EdgeKind,
+    pub change: Change,
+}
+
+pub fn diff_models(old: &Model, new: &Model) -> ModelDiff {
+    let mut elements = Vec::new();
+    for new_element in &new.elements {
+        let old_element = old.elements.iter().find(|old_element| {
+            old_element.name() == new_element.name() && old_element.module() == new_element.module()
+        });
+        let members = diff_members(old_element, Some(new_element));
+        let change = match old_element {
+            None => Change::Added,
+            Some(old_element)
+                if old_element.label() != new_element.label()
+                    || std::mem::discriminant(old_element)
+                        != std::mem::discriminant(new_element)
+                    || members.iter().any(|m| m.change != Change::Unchanged) =>
+            {
+                Change::Changed
+            }
+            Some(_) => Change::Unchanged,
+        };
+        elements.push(ElementDiff {
+            name: new_element.name().to_string(),
+            module: new_element.module().to_vec(),
+            label: new_element.label(),
+            change,
+            members,
+        });
+    }
+    for old_element in &old.elements {
+        let kept = new.elements.iter().any(|new_element| {
+            old_element.name() == new_element.name() && old_element.module() == new_element.module()
+        });
+        if !kept {
+            elements.push(ElementDiff {
+                name: old_element.name().to_string(),
+                module: old_element.module().to_vec(),
+                label: old_element.label(),
+                change: Change::Removed,
+                members: diff_members(Some(old_element), None),
+            });
+        }
+    }
+
+    ModelDiff {
+        elements,
+        edges: diff_edges(&edge_list(old), &edge_list(new)),
+    }
+}
+
+/// Members as `(name, rendered)` pairs, the name is what members are matched by.
+/// Derives come first as `#[derive(Trait)]`, so that gaining `Serialize` is a change too
+fn named_members(element: &Element) -> Vec<(String, String)> {
+    let derives = element.attrs().derives.iter().map(|derive| {
+        let derive = format!("#[derive({})]", derive);
+        (derive.clone(), derive)
+    });
+    let names: Vec<String> = match element {
+        Element::Struct(struct_element) => struct_element
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(position, field)| field.label(position))
+            .collect(),
+        Element::Enum(enum_element) => enum_element
+            .variants
+            .iter()
+            .map(|variant| variant.name.clone())
+            .collect(),
+        Element::Trait(trait_element) => trait_element.associated_types.clone(),
+    };
+    derives
+        .chain(names.into_iter().zip(members(element)))
+        .collect()
+}
+
+fn diff_members(old: Option<&Element>, new: Option<&Element>) -> Vec<MemberDiff> {
+    let old_members = old.map(named_members).unwrap_or_default();
+    let new_members = new.map(named_members).unwrap_or_default();
+
+    let mut diffs: Vec<MemberDiff> = new_members
+        .iter()
+        .map(|(name, new_member)| {
+            let old_member = old_members
+                .iter()
+                .find(|(old_name, _)| old_name == name)
+                .map(|(_, old_member)| old_member.clone());
+            let change = match &old_member {
+                None => Change::Added,
+                Some(old_member) if old_member != new_member => Change::Changed,
+                Some(_) => Change::Unchanged,
+            };
+            MemberDiff {
+                name: name.clone(),
+                change,
+                old: old_member,
+                new: Some(new_member.clone()),
+            }
+        })
+        .collect();
+    for (name, old_member) in &old_members {
+        if !new_members.iter().any(|(new_name, _)| new_name == name) {
+            diffs.push(MemberDiff {
+                name: name.clone(),
+                change: Change::Removed,
+                old: Some(old_member.clone()),
+                new: None,
+            });
+        }
+    }
+    diffs
+}
+
+/// `module::Name`, what edges are keyed by
+fn element_path(module: &[String], name: &str) -> String {
+    let mut path = module.to_vec();
+    path.push(name.to_string());
+    path.join("::")
+}
+
+/// Edges of the default graph as `(source, target, field, kind)`, endpoints by element path
+fn edge_list(model: &Model) -> Vec<(String, String, Option<String>, EdgeKind)> {
+    let elements = &model.elements;
+    let mut graph = DiGraph::<String, Edge>::new();
+    let node_indices = create_nodes_for_graph(elements, &mut graph);
+    let options = GraphOptions {
+        modules: model.modules.clone(),
+        ..GraphOptions::default()
+    };
+    add_edges_for_graph_with_options(elements, &mut graph, &node_indices, &options);
+
+    let name = |node| match node_indices.iter().position(|&i| i == node) {
+        Some(position) => element_path(elements[position].module(), elements[position].name()),
+        None => graph[node].clone(),
+    };
+    graph
+        .edge_references()
+        .map(|edge| {
+            (
+                name(edge.source()),
+                name(edge.target()),
+                edge.weight().field.clone(),
+                edge.weight().kind,
+            )
+        })
+        .collect()
+}
+
+fn diff_edges(
+    old: &[(String, String, Option<String>, EdgeKind)],
+    new: &[(String, String, Option<String>, EdgeKind)],
+) -> Vec<EdgeDiff> {
+    let mut remaining_old = old.to_vec();
+    let mut diffs = Vec::new();
+    for edge in new {
+        let change = match remaining_old.iter().position(|old_edge| old_edge == edge) {
+            Some(position) => {
+                remaining_old.remove(position);
+                Change::Unchanged
+            }
+            None => Change::Added,
+        };
+        diffs.push(edge_diff(edge, change));
+    }
+    diffs.extend(
+        remaining_old
+            .iter()
+            .map(|edge| edge_diff(edge, Change::Removed)),
+    );
+    diffs
+}
+
+fn edge_diff(edge: &(String, String, Option<String>, EdgeKind), change: Change) -> EdgeDiff {
+    let (source, target, field, kind) = edge.clone();
+    EdgeDiff {
+        source,
+        target,
+        field,
+        kind,
+        change,
+    }
+}
+
+impl ModelDiff {
+    pub fn is_empty(&self) -> bool {
+        self.elements.iter().all(|e| e.change == Change::Unchanged)
+            && self.edges.iter().all(|e| e.change == Change::Unchanged)
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added => write!(f, "added"),
+            Change::Removed => write!(f, "removed"),
+            Change::Changed => write!(f, "changed"),
+            Change::Unchanged => write!(f, "unchanged"),
+        }
+    }
+}
+
+/// One line per added, removed or changed element, followed by its member changes, then one
+/// line per added or removed edge:
+/// ```text
+/// changed Order
+///     changed + total: u32 -> + total: Money
+///     added + status: Status
+/// added Money
+/// added edge Order.total -> Money
+/// ```
+impl fmt::Display for ModelDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for element in &self.elements {
+            if element.change == Change::Unchanged {
+                continue;
+            }
+            let mut path = element.module.clone();
+            path.push(element.label.clone());
+            writeln!(f, "{} {}", element.change, path.join("::"))?;
+            // The members of added and removed elements are implied
+            if element.change != Change::Changed {
+                continue;
+            }
+            for member in &element.members {
+                let old = member.old.as_deref().unwrap_or_default();
+                let new = member.new.as_deref().unwrap_or_default();
+                match member.change {
+                    Change::Added => writeln!(f, "    added {}", new)?,
+                    Change::Removed => writeln!(f, "    removed {}", old)?,
+                    Change::Changed => writeln!(f, "    changed {} -> {}", old, new)?,
+                    Change::Unchanged => {}
+                }
+            }
+        }
+        for edge in &self.edges {
+            if edge.change == Change::Unchanged {
+                continue;
+            }
+            write!(f, "{} edge {}", edge.change, edge.source)?;
+            if let Some(field) = &edge.field {
+                write!(f, ".{}", field.trim_start_matches('.'))?;
+            }
+            writeln!(f, " -> {}", edge.target)?;
+        }
+        Ok(())
+    }
+}
+
+fn change_color(change: Change) -> &'static str {
+    match change {
+        Change::Added => "darkgreen",
+        Change::Removed => "red",
+        Change::Changed => "darkorange",
+        Change::Unchanged => "black",
+    }
+}
+
+/// Both versions in one Graphviz diagram: additions in green, removals in red (struck out for
+/// members) and changed elements in orange, with the old member shown struck out next to the new
+pub fn diff_to_dot(diff: &ModelDiff) -> String {
+    let mut output = String::from("digraph {\n    node [ shape = plaintext ]\n");
+    for (index, element) in diff.elements.iter().enumerate() {
+        let color = change_color(element.change);
+        let mut rows = vec![format!(
+            "<tr><td><font color=\"{}\"><b>{}</b></font></td></tr>",
+            color,
+            escape_html(&element.label)
+        )];
+        for member in &element.members {
+            let old = member.old.as_deref().map(escape_html).unwrap_or_default();
+            let new = member.new.as_deref().map(escape_html).unwrap_or_default();
+            let text = match member.change {
+                Change::Removed => format!("<s>{}</s>", old),
+                Change::Changed => format!("<s>{}</s> {}", old, new),
+                Change::Added | Change::Unchanged => new,
+            };
+            rows.push(format!(
+                "<tr><td align=\"left\"><font color=\"{}\">{}</font></td></tr>",
+                change_color(member.change),
+                text
+            ));
+        }
+        output.push_str(&format!(
+            "    {} [ label = <<table border=\"1\" cellborder=\"0\" color=\"{}\">{}</table>> ]\n",
+            index,
+            color,
+            rows.join("")
+        ));
+    }
+    for edge in &diff.edges {
+        let node = |path: &str| {
+            diff.elements
+                .iter()
+                .position(|e| element_path(&e.module, &e.name) == path)
+        };
+        if let (Some(source), Some(target)) = (node(&edge.source), node(&edge.target)) {
+            output.push_str(&format!(
+                "    {} -> {} [ color = \"{}\" fontcolor = \"{}\" label = \"{}\" ]\n",
+                source,
+                target,
+                change_color(edge.change),
+                change_color(edge.change),
+                edge.field
+                    .as_deref()
+                    .unwrap_or_default()
+                    .replace('"', "\\\"")
+            ));
+        }
+    }
+    output.push_str("}\n");
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OLD_CODE: &str = "
+        pub struct Order {
+            pub id: u64,
+            pub total: u32,
+            pub note: String,
+        }
+
+        pub enum Status {
+            Open,
+        }
+
+        pub struct Legacy {}
+    ";
+
+    const NEW_CODE: &str = "
+        pub struct Order {
+            pub id: u64,
+            pub total: Money,
+            pub status: Status,
+        }
+
+        pub enum Status {
+            Open,
+        }
+
+        pub struct Money {}
+    ";
+
+    #[test]
+    fn test_diff_models() {
+        let old = collect_model(parse_rust_code(OLD_CODE));
+        let new = collect_model(parse_rust_code(NEW_CODE));
+        let diff = diff_models(&old, &new);
+
+        let elements: Vec<(&str, Change)> = diff
+            .elements
+            .iter()
+            .map(|e| (e.name.as_str(), e.change))
+            .collect();
+        assert_eq!(
+            elements,
+            vec![
+                ("Order", Change::Changed),
+                ("Status", Change::Unchanged),
+                ("Money", Change::Added),
+                ("Legacy", Change::Removed),
+            ]
+        );
+
+        let members: Vec<(&str, Change)> = diff.elements[0]
+            .members
+            .iter()
+            .map(|m| (m.name.as_str(), m.change))
+            .collect();
+        assert_eq!(
+            members,
+            vec![
+                ("id", Change::Unchanged),
+                ("total", Change::Changed),
+                ("status", Change::Added),
+                ("note", Change::Removed),
+            ]
+        );
+        assert_eq!(
+            diff.elements[0].members[1].old,
+            Some("+ total: u32".to_string())
+        );
+
+        let edges: Vec<(&str, &str, Change)> = diff
+            .edges
+            .iter()
+            .map(|e| (e.source.as_str(), e.target.as_str(), e.change))
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                ("Order", "Money", Change::Added),
+                ("Order", "Status", Change::Added),
+            ]
+        );
+        assert!(!diff.is_empty());
+        assert!(diff_models(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_diff_same_names_in_modules() {
+        let code = |field: &str| {
+            format!(
+                "
+                pub mod a {{
+                    pub struct Id {{}}
+                }}
+                pub mod b {{
+                    pub struct Id {{}}
+                }}
+                pub struct Order {{
+                    pub id: {},
+                }}
+                ",
+                field
+            )
+        };
+        let old = collect_model(parse_rust_code(&code("a::Id")));
+        let new = collect_model(parse_rust_code(&code("b::Id")));
+        let diff = diff_models(&old, &new);
+
+        let edges: Vec<(&str, &str, Change)> = diff
+            .edges
+            .iter()
+            .map(|e| (e.source.as_str(), e.target.as_str(), e.change))
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                ("Order", "b::Id", Change::Added),
+                ("Order", "a::Id", Change::Removed),
+            ]
+        );
+        let dot = diff_to_dot(&diff);
+        assert!(dot.contains("0 -> 2 [ color = \"darkgreen\""));
+        assert!(dot.contains("0 -> 1 [ color = \"red\""));
+    }
+
+    #[test]
+    fn test_diff_to_dot() {
+        let old = collect_model(parse_rust_code(OLD_CODE));
+        let new = collect_model(parse_rust_code(NEW_CODE));
+        let dot = diff_to_dot(&diff_models(&old, &new));
+
+        assert!(
+            dot.contains("<font color=\"darkorange\"><s>+ total: u32</s> + total: Money</font>")
+        );
+        assert!(dot.contains("<font color=\"red\"><b>Legacy</b></font>"));
+        assert!(dot.contains(
+            "0 -> 2 [ color = \"darkgreen\" fontcolor = \"darkgreen\" label = \"total\" ]"
+        ));
+    }
+
+    #[test]
+    fn test_diff_text() {
+        let old = collect_model(parse_rust_code(OLD_CODE));
+        let new = collect_model(parse_rust_code(NEW_CODE));
+
+        assert_eq!(
+            diff_models(&old, &new).to_string(),
+            "changed Order\n    \
+             changed + total: u32 -> + total: Money\n    \
+             added + status: Status\n    \
+             removed + note: String\n\
+             added Money\n\
+             removed Legacy\n\
+             added edge Order.total -> Money\n\
+             added edge Order.status -> Status\n"
+        );
+        assert_eq!(diff_models(&old, &old).to_string(), "");
+    }
+}
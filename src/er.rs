@@ -0,0 +1,507 @@
+use crate::export::escape_html;
+use crate::structures::*;
+use syn::punctuated::Punctuated;
+use syn::{Expr, ExprLit, Lit, Meta, Token};
+
+/// Entity-relationship view of the persistence structs of a model
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ErDiagram {
+    pub entities: Vec<Entity>,
+    pub relationships: Vec<Relationship>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Entity {
+    /// Struct name
+    pub name: String,
+    /// `table_name` attribute, otherwise the snake_case struct name with an `s`, as diesel
+    /// does: `users` for `User`
+    pub table: String,
+    pub attributes: Vec<EntityAttribute>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct EntityAttribute {
+    pub name: String,
+    /// Type with `Option` removed, see `nullable`
+    pub ty: String,
+    pub nullable: bool,
+    pub keys: Vec<Key>,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Key {
+    Primary,
+    Foreign,
+}
+
+/// `from` and `to` are entity names, each cardinality tells how many of that side take part:
+/// `Comment }o--|| Post` is `from_cardinality: ZeroOrMore, to_cardinality: ExactlyOne`
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Relationship {
+    pub from: String,
+    pub to: String,
+    /// Foreign key or field the relationship comes from
+    pub label: String,
+    pub from_cardinality: Cardinality,
+    pub to_cardinality: Cardinality,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Cardinality {
+    ZeroOrOne,
+    ExactlyOne,
+    ZeroOrMore,
+    OneOrMore,
+}
+
+/// Derives that make a struct a database entity (diesel and sqlx)
+const ENTITY_DERIVES: &[&str] = &[
+    "Queryable",
+    "QueryableByName",
+    "Insertable",
+    "Identifiable",
+    "Selectable",
+    "AsChangeset",
+    "Associations",
+    "FromRow",
+];
+
+/// Attributes of the struct with `diesel(...)` unwrapped, so diesel 1 (`#[table_name = "t"]`)
+/// and diesel 2 (`#[diesel(table_name = t)]`) look the same
+fn persistence_attrs(attrs: &Attributes) -> Vec<Meta> {
+    attrs
+        .other
+        .iter()
+        .filter_map(|attr| syn::parse_str::<Meta>(attr).ok())
+        .flat_map(|meta| match &meta {
+            Meta::List(list) if list.path.is_ident("diesel") || list.path.is_ident("sqlx") => list
+                .parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+                .map(|nested| nested.into_iter().collect())
+                .unwrap_or_default(),
+            _ => vec![meta],
+        })
+        .collect()
+}
+
+fn is_entity(struct_element: &StructElement) -> bool {
+    struct_element.attrs.derives.iter().any(|derive| {
+        let name = derive.rsplit("::").next().unwrap_or(derive).trim();
+        ENTITY_DERIVES.contains(&name)
+    }) || persistence_attrs(&struct_element.attrs)
+        .iter()
+        .any(|meta| meta.path().is_ident("table_name"))
+}
+
+/// `name = "value"` or `name = path` as text
+fn meta_value(meta: &Meta) -> Option<String> {
+    match meta {
+        Meta::NameValue(name_value) => match &name_value.value {
+            Expr::Lit(ExprLit {
+                lit: Lit::Str(value),
+                ..
+            }) => Some(value.value()),
+            Expr::Path(path) => path.path.segments.last().map(|s| s.ident.to_string()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Idents inside `name(a, b)`, for `primary_key(...)` and `belongs_to(...)`
+fn meta_list(meta: &Meta) -> Vec<Meta> {
+    match meta {
+        Meta::List(list) => list
+            .parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+            .map(|nested| nested.into_iter().collect())
+            .unwrap_or_default(),
+        _ => Vec::new(),
+    }
+}
+
+/// `OrderLine` becomes `order_line`
+fn snake_case(name: &str) -> String {
+    let mut snake = String::new();
+    for (position, c) in name.char_indices() {
+        if position > 0 && c.is_uppercase() {
+            snake.push('_');
+        }
+        snake.extend(c.to_lowercase());
+    }
+    snake
+}
+
+fn meta_ident(meta: &Meta) -> Option<String> {
+    match meta {
+        Meta::Path(path) => path.segments.last().map(|s| s.ident.to_string()),
+        _ => None,
+    }
+}
+
+/// Structs with diesel or sqlx derives or a `table_name` are entities; when the model has none,
+/// every struct with named fields is. Primary keys come from `primary_key(...)` or an `id`
+/// field, foreign keys from `belongs_to(Parent, foreign_key = f)` or `<entity>_id` fields.
+/// Fields holding another entity are relationships too, `Vec<T>` ones one-to-many
+pub fn er_diagram(model: &Model) -> ErDiagram {
+    let structs: Vec<&StructElement> = model
+        .elements
+        .iter()
+        .filter_map(|element| match element {
+            Element::Struct(struct_element) => Some(struct_element),
+            _ => None,
+        })
+        .collect();
+    let marked: Vec<&StructElement> = structs.iter().copied().filter(|s| is_entity(s)).collect();
+    let entity_structs = if marked.is_empty() {
+        structs
+            .into_iter()
+            .filter(|s| s.fields.first().is_some_and(|f| f.name.is_some()))
+            .collect()
+    } else {
+        marked
+    };
+
+    let tables: Vec<(String, String)> = entity_structs
+        .iter()
+        .map(|s| {
+            let table = persistence_attrs(&s.attrs)
+                .iter()
+                .filter(|meta| meta.path().is_ident("table_name"))
+                .find_map(meta_value)
+                .unwrap_or_else(|| format!("{}s", snake_case(&s.name)));
+            (s.name.clone(), table)
+        })
+        .collect();
+    let entity_for_prefix = |prefix: &str| {
+        tables
+            .iter()
+            .find(|(name, table)| {
+                snake_case(name) == prefix || table == prefix || *table == format!("{}s", prefix)
+            })
+            .map(|(name, _)| name.clone())
+    };
+    let is_entity_name = |name: &str| tables.iter().any(|(entity, _)| entity == name);
+
+    let mut diagram = ErDiagram::default();
+    for (struct_element, (_, table)) in entity_structs.iter().zip(&tables) {
+        let attrs = persistence_attrs(&struct_element.attrs);
+        let mut primary_keys: Vec<String> = attrs
+            .iter()
+            .filter(|meta| meta.path().is_ident("primary_key"))
+            .flat_map(meta_list)
+            .filter_map(|meta| meta_ident(&meta))
+            .collect();
+        if primary_keys.is_empty() {
+            primary_keys.push("id".to_string());
+        }
+        let mut foreign_keys: Vec<(String, String)> = attrs
+            .iter()
+            .filter(|meta| meta.path().is_ident("belongs_to"))
+            .filter_map(|meta| {
+                let nested = meta_list(meta);
+                let parent = nested.first().and_then(meta_ident)?;
+                let key = nested
+                    .iter()
+                    .filter(|meta| meta.path().is_ident("foreign_key"))
+                    .find_map(meta_value)
+                    .unwrap_or_else(|| format!("{}_id", snake_case(&parent)));
+                Some((key, parent))
+            })
+            .collect();
+
+        let mut attributes = Vec::new();
+        for (position, field) in struct_element.fields.iter().enumerate() {
+            let name = field.label(position);
+            let (ty, nullable) = match &field.ty {
+                Type::Generic(wrapper, args) if wrapper == "Option" && args.len() == 1 => {
+                    (&args[0], true)
+                }
+                ty => (ty, false),
+            };
+            let parent = foreign_keys
+                .iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, parent)| parent.clone())
+                .or_else(|| name.strip_suffix("_id").and_then(entity_for_prefix));
+            let mut keys = Vec::new();
+            if primary_keys.contains(&name) {
+                keys.push(Key::Primary);
+            }
+            if let Some(parent) = parent {
+                keys.push(Key::Foreign);
+                foreign_keys.retain(|(key, _)| *key != name);
+                diagram.relationships.push(Relationship {
+                    from: struct_element.name.clone(),
+                    to: parent,
+                    label: name.clone(),
+                    from_cardinality: Cardinality::ZeroOrMore,
+                    to_cardinality: if nullable {
+                        Cardinality::ZeroOrOne
+                    } else {
+                        Cardinality::ExactlyOne
+                    },
+                });
+            }
+            match ty {
+                Type::Simple(target) if is_entity_name(target) => {
+                    diagram.relationships.push(Relationship {
+                        from: struct_element.name.clone(),
+                        to: target.clone(),
+                        label: name.clone(),
+                        from_cardinality: Cardinality::ZeroOrMore,
+                        to_cardinality: if nullable {
+                            Cardinality::ZeroOrOne
+                        } else {
+                            Cardinality::ExactlyOne
+                        },
+                    })
+                }
+                Type::Vec(inner) => {
+                    if let Type::Simple(target) = inner.as_ref() {
+                        if is_entity_name(target) {
+                            diagram.relationships.push(Relationship {
+                                from: struct_element.name.clone(),
+                                to: target.clone(),
+                                label: name.clone(),
+                                from_cardinality: Cardinality::ExactlyOne,
+                                to_cardinality: Cardinality::ZeroOrMore,
+                            })
+                        }
+                    }
+                }
+                _ => {}
+            }
+            attributes.push(EntityAttribute {
+                name,
+                ty: ty.to_string(),
+                nullable,
+                keys,
+            });
+        }
+        diagram.entities.push(Entity {
+            name: struct_element.name.clone(),
+            table: table.clone(),
+            attributes,
+        });
+    }
+    diagram
+}
+
+impl Cardinality {
+    /// Mermaid marker on the left (`left`) or right side of `--`
+    fn mermaid(self, left: bool) -> &'static str {
+        match (self, left) {
+            (Cardinality::ZeroOrOne, true) => "|o",
+            (Cardinality::ZeroOrOne, false) => "o|",
+            (Cardinality::ExactlyOne, _) => "||",
+            (Cardinality::ZeroOrMore, true) => "}o",
+            (Cardinality::ZeroOrMore, false) => "o{",
+            (Cardinality::OneOrMore, true) => "}|",
+            (Cardinality::OneOrMore, false) => "|{",
+        }
+    }
+
+    /// Graphviz crow's foot arrow shape
+    fn dot(self) -> &'static str {
+        match self {
+            Cardinality::ZeroOrOne => "teeodot",
+            Cardinality::ExactlyOne => "teetee",
+            Cardinality::ZeroOrMore => "crowodot",
+            Cardinality::OneOrMore => "crowtee",
+        }
+    }
+}
+
+/// Mermaid only allows words in attribute types, `Vec<u8>` becomes `Vec_u8`
+fn mermaid_type(ty: &str) -> String {
+    let word: String = ty
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '_' })
+        .collect();
+    let word = word.trim_matches('_');
+    let mut collapsed = String::new();
+    for c in word.chars() {
+        if !(c == '_' && collapsed.ends_with('_')) {
+            collapsed.push(c);
+        }
+    }
+    collapsed
+}
+
+fn key_markers(keys: &[Key]) -> Vec<&'static str> {
+    keys.iter()
+        .map(|key| match key {
+            Key::Primary => "PK",
+            Key::Foreign => "FK",
+        })
+        .collect()
+}
+
+pub fn er_to_mermaid(diagram: &ErDiagram) -> String {
+    let mut output = String::from("erDiagram\n");
+    for entity in &diagram.entities {
+        output.push_str(&format!("    {} {{\n", entity.name));
+        for attribute in &entity.attributes {
+            let mut line = format!("        {} {}", mermaid_type(&attribute.ty), attribute.name);
+            let keys = key_markers(&attribute.keys);
+            if !keys.is_empty() {
+                line.push_str(&format!(" {}", keys.join(", ")));
+            }
+            if attribute.nullable {
+                line.push_str(" \"nullable\"");
+            }
+            output.push_str(&line);
+            output.push('\n');
+        }
+        output.push_str("    }\n");
+    }
+    for relationship in &diagram.relationships {
+        output.push_str(&format!(
+            "    {} {}--{} {} : \"{}\"\n",
+            relationship.from,
+            relationship.from_cardinality.mermaid(true),
+            relationship.to_cardinality.mermaid(false),
+            relationship.to,
+            relationship.label
+        ));
+    }
+    output
+}
+
+/// Entities as tables with the key markers in the first column, relationships with crow's foot
+/// arrows at both ends
+pub fn er_to_dot(diagram: &ErDiagram) -> String {
+    let mut output = String::from("digraph {\n    node [ shape = plaintext ]\n");
+    for (index, entity) in diagram.entities.iter().enumerate() {
+        let mut rows = vec![format!(
+            "<tr><td colspan=\"3\"><b>{}</b> ({})</td></tr>",
+            escape_html(&entity.name),
+            escape_html(&entity.table)
+        )];
+        for attribute in &entity.attributes {
+            let ty = if attribute.nullable {
+                format!("{}?", attribute.ty)
+            } else {
+                attribute.ty.clone()
+            };
+            rows.push(format!(
+                "<tr><td>{}</td><td align=\"left\">{}</td><td align=\"left\">{}</td></tr>",
+                key_markers(&attribute.keys).join(", "),
+                escape_html(&attribute.name),
+                escape_html(&ty)
+            ));
+        }
+        output.push_str(&format!(
+            "    {} [ label = <<table border=\"1\" cellborder=\"0\">{}</table>> ]\n",
+            index,
+            rows.join("")
+        ));
+    }
+    for relationship in &diagram.relationships {
+        let node = |name: &str| diagram.entities.iter().position(|e| e.name == name);
+        if let (Some(from), Some(to)) = (node(&relationship.from), node(&relationship.to)) {
+            output.push_str(&format!(
+                "    {} -> {} [ dir = both arrowtail = {} arrowhead = {} label = \"{}\" ]\n",
+                from,
+                to,
+                relationship.from_cardinality.dot(),
+                relationship.to_cardinality.dot(),
+                relationship.label
+            ));
+        }
+    }
+    output.push_str("}\n");
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::funcs::*;
+
+    const TEST_CODE: &str = r#"
+        #[derive(Queryable, Identifiable)]
+        #[diesel(table_name = users)]
+        pub struct User {
+            pub id: i32,
+            pub name: String,
+        }
+
+        #[derive(Queryable, Associations)]
+        #[diesel(belongs_to(User, foreign_key = author_id))]
+        pub struct Post {
+            pub id: i32,
+            pub author_id: i32,
+            pub editor_id: Option<i32>,
+            pub comments: Vec<Comment>,
+        }
+
+        #[derive(sqlx::FromRow)]
+        #[table_name = "comments"]
+        #[primary_key(post_id, position)]
+        pub struct Comment {
+            pub post_id: i32,
+            pub position: i32,
+        }
+
+        pub struct Editor {}
+    "#;
+
+    #[test]
+    fn test_er_diagram() {
+        let diagram = er_diagram(&collect_model(parse_rust_code(TEST_CODE)));
+
+        let entities: Vec<(&str, &str)> = diagram
+            .entities
+            .iter()
+            .map(|e| (e.name.as_str(), e.table.as_str()))
+            .collect();
+        assert_eq!(
+            entities,
+            vec![
+                ("User", "users"),
+                ("Post", "posts"),
+                ("Comment", "comments")
+            ]
+        );
+        let keys: Vec<(&str, &[Key])> = diagram.entities[2]
+            .attributes
+            .iter()
+            .map(|a| (a.name.as_str(), a.keys.as_slice()))
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                ("post_id", &[Key::Primary, Key::Foreign][..]),
+                ("position", &[Key::Primary][..])
+            ]
+        );
+        let relationships: Vec<(&str, &str, &str)> = diagram
+            .relationships
+            .iter()
+            .map(|r| (r.from.as_str(), r.to.as_str(), r.label.as_str()))
+            .collect();
+        assert_eq!(
+            relationships,
+            vec![
+                ("Post", "User", "author_id"),
+                ("Post", "Comment", "comments"),
+                ("Comment", "Post", "post_id"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_er_to_mermaid() {
+        let diagram = er_diagram(&collect_model(parse_rust_code(TEST_CODE)));
+        let mermaid = er_to_mermaid(&diagram);
+
+        assert!(mermaid.starts_with("erDiagram\n    User {\n        i32 id PK\n"));
+        assert!(mermaid.contains("        i32 editor_id \"nullable\"\n"));
+        assert!(mermaid.contains("        Vec_Comment comments\n"));
+        assert!(mermaid.contains("    Post }o--|| User : \"author_id\"\n"));
+        assert!(mermaid.contains("    Post ||--o{ Comment : \"comments\"\n"));
+        assert!(er_to_dot(&diagram)
+            .contains("1 -> 0 [ dir = both arrowtail = crowodot arrowhead = teetee"));
+    }
+}
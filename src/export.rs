@@ -0,0 +1,687 @@
+use crate::structures::*;
+use petgraph::graph::{DiGraph, EdgeIndex, NodeIndex};
+use petgraph::visit::EdgeRef;
+
+#[derive(Debug, Default, Clone)]
+pub struct ExportOptions {
+    /// List fields, variants and associated types inside the nodes, with UML visibility markers
+    pub members: bool,
+    /// Group the nodes by module: DOT clusters, PlantUML packages, Mermaid namespaces
+    pub clusters: bool,
+    /// Edges drawn in red (the edges of `find_cycles`), in DOT and PlantUML
+    pub highlighted_edges: Vec<EdgeIndex>,
+}
+
+/// Renders the graph built by `create_nodes_for_graph` and `add_edges_for_graph` to Graphviz.
+/// Doc comments of elements become node tooltips
+pub fn to_dot(
+    elements: &[Element],
+    graph: &DiGraph<String, Edge>,
+    node_indices: &[NodeIndex],
+    options: &ExportOptions,
+) -> String {
+    let mut output = String::from("digraph {\n");
+    let tree = module_tree(elements, graph, node_indices, options.clusters);
+    write_dot_module(
+        &tree,
+        elements,
+        graph,
+        node_indices,
+        options,
+        1,
+        &mut output,
+    );
+    for edge in graph.edge_references() {
+        output.push_str(&format!(
+            "    {} -> {} [ {} ]\n",
+            edge.source().index(),
+            edge.target().index(),
+            match options.highlighted_edges.contains(&edge.id()) {
+                true => format!("{} color = red penwidth = 2", edge_attrs(edge.weight()))
+                    .trim_start()
+                    .to_string(),
+                false => edge_attrs(edge.weight()),
+            }
+        ));
+    }
+    output.push_str("}\n");
+    output
+}
+
+fn write_dot_module(
+    tree: &ModuleTree,
+    elements: &[Element],
+    graph: &DiGraph<String, Edge>,
+    node_indices: &[NodeIndex],
+    options: &ExportOptions,
+    depth: usize,
+    output: &mut String,
+) {
+    let indent = "    ".repeat(depth);
+    // Leaf nodes of external types labelled `crate::Type` are grouped by crate
+    let mut crates: Vec<(&str, Vec<(NodeIndex, &str)>)> = Vec::new();
+    for &node in &tree.nodes {
+        let attrs = match element_of(elements, node_indices, node) {
+            Some(element) => element_node_attrs(element, options),
+            None if is_leaf(graph, node) => match graph[node].split_once("::") {
+                Some((krate, name)) => {
+                    match crates.iter_mut().find(|(other, _)| *other == krate) {
+                        Some((_, nodes)) => nodes.push((node, name)),
+                        None => crates.push((krate, vec![(node, name)])),
+                    }
+                    continue;
+                }
+                None => leaf_node_attrs(&graph[node]),
+            },
+            None => format!("label = \"{}\"", escape(&graph[node])),
+        };
+        output.push_str(&format!("{}{} [ {} ]\n", indent, node.index(), attrs));
+    }
+    for (krate, nodes) in crates {
+        output.push_str(&format!(
+            "{}subgraph \"cluster_crate_{}\" {{\n{}    label = \"{}\"\n{}    style = dashed\n",
+            indent, krate, indent, krate, indent
+        ));
+        for (node, name) in nodes {
+            output.push_str(&format!(
+                "{}    {} [ {} ]\n",
+                indent,
+                node.index(),
+                leaf_node_attrs(name)
+            ));
+        }
+        output.push_str(&format!("{}}}\n", indent));
+    }
+    for child in &tree.children {
+        let path = child.path.join("::");
+        output.push_str(&format!(
+            "{}subgraph \"cluster_{}\" {{\n{}    label = \"{}\"\n",
+            indent, path, indent, path
+        ));
+        write_dot_module(
+            child,
+            elements,
+            graph,
+            node_indices,
+            options,
+            depth + 1,
+            output,
+        );
+        output.push_str(&format!("{}}}\n", indent));
+    }
+}
+
+/// Synthesized node without outgoing edges: an external or primitive type, or the
+/// placeholder of filtered types
+fn is_leaf(graph: &DiGraph<String, Edge>, node: NodeIndex) -> bool {
+    graph.neighbors(node).next().is_none()
+}
+
+fn leaf_node_attrs(label: &str) -> String {
+    format!(
+        "label = \"{}\" style = dashed color = gray50 fontcolor = gray50",
+        escape(label)
+    )
+}
+
+/// Element a node was created for, `None` for synthesized nodes
+fn element_of<'a>(
+    elements: &'a [Element],
+    node_indices: &[NodeIndex],
+    node: NodeIndex,
+) -> Option<&'a Element> {
+    node_indices
+        .iter()
+        .position(|&i| i == node)
+        .map(|position| &elements[position])
+}
+
+/// Nodes of a module and its submodules, only modules that hold nodes (directly or below) appear
+struct ModuleTree {
+    path: Vec<String>,
+    nodes: Vec<NodeIndex>,
+    children: Vec<ModuleTree>,
+}
+
+/// Every node in the root when `clusters` is false. Synthesized nodes always stay in the root
+fn module_tree(
+    elements: &[Element],
+    graph: &DiGraph<String, Edge>,
+    node_indices: &[NodeIndex],
+    clusters: bool,
+) -> ModuleTree {
+    let mut root = ModuleTree {
+        path: Vec::new(),
+        nodes: Vec::new(),
+        children: Vec::new(),
+    };
+    for node in graph.node_indices() {
+        let module = match element_of(elements, node_indices, node) {
+            Some(element) if clusters => element.module(),
+            _ => &[],
+        };
+        let mut tree = &mut root;
+        for depth in 1..=module.len() {
+            let path = &module[..depth];
+            let position = match tree.children.iter().position(|child| child.path == path) {
+                Some(position) => position,
+                None => {
+                    tree.children.push(ModuleTree {
+                        path: path.to_vec(),
+                        nodes: Vec::new(),
+                        children: Vec::new(),
+                    });
+                    tree.children.len() - 1
+                }
+            };
+            tree = &mut tree.children[position];
+        }
+        tree.nodes.push(node);
+    }
+    root
+}
+
+/// PlantUML class diagram of the same graph. Nested packages follow the module tree
+pub fn to_plantuml(
+    elements: &[Element],
+    graph: &DiGraph<String, Edge>,
+    node_indices: &[NodeIndex],
+    options: &ExportOptions,
+) -> String {
+    let mut output = String::from("@startuml\n");
+    let tree = module_tree(elements, graph, node_indices, options.clusters);
+    write_plantuml_module(
+        &tree,
+        elements,
+        graph,
+        node_indices,
+        options,
+        0,
+        &mut output,
+    );
+    for edge in graph.edge_references() {
+        let field = edge.weight().field.as_deref().unwrap_or_default();
+        let (arrow, label) = match edge.weight().kind {
+            EdgeKind::Field => ("-->", field),
+            EdgeKind::BoundWith => ("..>", "bound with"),
+            EdgeKind::Binding => ("..|>", "«bind»"),
+            EdgeKind::AssociatedType => ("..>", field),
+        };
+        // `-[#red]->`, `.[#red].>`
+        let arrow = match options.highlighted_edges.contains(&edge.id()) {
+            true => format!("{}[#red]{}", &arrow[..1], &arrow[1..]),
+            false => arrow.to_string(),
+        };
+        output.push_str(&format!(
+            "N{} {} N{}",
+            edge.source().index(),
+            arrow,
+            edge.target().index()
+        ));
+        if !label.is_empty() {
+            output.push_str(&format!(" : {}", label));
+        }
+        output.push('\n');
+    }
+    output.push_str("@enduml\n");
+    output
+}
+
+fn write_plantuml_module(
+    tree: &ModuleTree,
+    elements: &[Element],
+    graph: &DiGraph<String, Edge>,
+    node_indices: &[NodeIndex],
+    options: &ExportOptions,
+    depth: usize,
+    output: &mut String,
+) {
+    let indent = "  ".repeat(depth);
+    for &node in &tree.nodes {
+        let element = element_of(elements, node_indices, node);
+        let (keyword, label) = match element {
+            Some(Element::Struct(_)) | None => ("class", graph[node].clone()),
+            Some(Element::Enum(_)) => ("enum", graph[node].clone()),
+            Some(Element::Trait(_)) => ("interface", graph[node].clone()),
+        };
+        output.push_str(&format!(
+            "{}{} \"{}\" as N{}",
+            indent,
+            keyword,
+            label.replace('"', "'"),
+            node.index()
+        ));
+        match element {
+            Some(element) if options.members => {
+                output.push_str(" {\n");
+                for member in members(element) {
+                    output.push_str(&format!("{}  {}\n", indent, member));
+                }
+                output.push_str(&format!("{}}}\n", indent));
+            }
+            _ => output.push('\n'),
+        }
+    }
+    for child in &tree.children {
+        let name = child.path.last().map(String::as_str).unwrap_or_default();
+        output.push_str(&format!("{}package {} {{\n", indent, name));
+        write_plantuml_module(
+            child,
+            elements,
+            graph,
+            node_indices,
+            options,
+            depth + 1,
+            output,
+        );
+        output.push_str(&format!("{}}}\n", indent));
+    }
+}
+
+/// Mermaid class diagram of the same graph. Mermaid namespaces don't nest, so every module
+/// gets a namespace of its own named by its full path (`a.b`)
+pub fn to_mermaid(
+    elements: &[Element],
+    graph: &DiGraph<String, Edge>,
+    node_indices: &[NodeIndex],
+    options: &ExportOptions,
+) -> String {
+    let mut output = String::from("classDiagram\n");
+    let tree = module_tree(elements, graph, node_indices, options.clusters);
+    write_mermaid_module(&tree, graph, &mut output);
+    for node in graph.node_indices() {
+        let Some(element) = element_of(elements, node_indices, node) else {
+            continue;
+        };
+        if let Some(annotation) = match element {
+            Element::Struct(_) => None,
+            Element::Enum(_) => Some("enumeration"),
+            Element::Trait(_) => Some("interface"),
+        } {
+            output.push_str(&format!("    <<{}>> N{}\n", annotation, node.index()));
+        }
+        if options.members {
+            for member in members(element) {
+                output.push_str(&format!(
+                    "    N{} : {}\n",
+                    node.index(),
+                    mermaid_generics(&member)
+                ));
+            }
+        }
+    }
+    for edge in graph.edge_references() {
+        let field = edge.weight().field.as_deref().unwrap_or_default();
+        let (arrow, label) = match edge.weight().kind {
+            EdgeKind::Field => ("-->", field),
+            EdgeKind::BoundWith => ("..>", "bound with"),
+            EdgeKind::Binding => ("..|>", "bind"),
+            EdgeKind::AssociatedType => ("..>", field),
+        };
+        output.push_str(&format!(
+            "    N{} {} N{}",
+            edge.source().index(),
+            arrow,
+            edge.target().index()
+        ));
+        if !label.is_empty() {
+            output.push_str(&format!(" : {}", label));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+fn write_mermaid_module(tree: &ModuleTree, graph: &DiGraph<String, Edge>, output: &mut String) {
+    let indent = if tree.path.is_empty() {
+        "    "
+    } else {
+        "        "
+    };
+    if !tree.path.is_empty() && !tree.nodes.is_empty() {
+        output.push_str(&format!("    namespace {} {{\n", tree.path.join(".")));
+    }
+    for &node in &tree.nodes {
+        output.push_str(&format!(
+            "{}class N{}[\"{}\"]\n",
+            indent,
+            node.index(),
+            mermaid_generics(&graph[node]).replace('"', "'")
+        ));
+    }
+    if !tree.path.is_empty() && !tree.nodes.is_empty() {
+        output.push_str("    }\n");
+    }
+    for child in &tree.children {
+        write_mermaid_module(child, graph, output);
+    }
+}
+
+/// Mermaid writes generics as `Vec~T~`
+fn mermaid_generics(text: &str) -> String {
+    text.replace(['<', '>'], "~")
+}
+
+fn element_node_attrs(element: &Element, options: &ExportOptions) -> String {
+    let mut attrs = if options.members {
+        let header: Vec<String> = stereotype(element)
+            .into_iter()
+            .map(String::from)
+            .chain([format!(
+                "{} {}",
+                element.vis().uml_marker(),
+                element.label()
+            )])
+            .map(|line| escape_record(&line))
+            .collect();
+        let members: String = members(element)
+            .iter()
+            .map(|line| format!("{}\\l", escape_record(line)))
+            .collect();
+        format!(
+            "shape = record label = \"{{{}|{}}}\"",
+            header.join("\\n"),
+            members
+        )
+    } else {
+        format!("label = \"{}\"", escape(&element.label()))
+    };
+    if let Some(docs) = &element.attrs().docs {
+        attrs.push_str(&format!(" tooltip = \"{}\"", escape(docs)));
+    }
+    attrs
+}
+
+fn edge_attrs(edge: &Edge) -> String {
+    let field = edge.field.as_deref().map(escape).unwrap_or_default();
+    match edge.kind {
+        EdgeKind::Field if field.is_empty() => String::new(),
+        EdgeKind::Field => format!("label = \"{}\"", field),
+        EdgeKind::BoundWith => "style = dashed label = \"bound with\"".to_string(),
+        EdgeKind::Binding => "style = dashed label = \"«bind»\"".to_string(),
+        EdgeKind::AssociatedType => format!("style = dotted label = \"{}\"", field),
+    }
+}
+
+fn stereotype(element: &Element) -> Option<&'static str> {
+    match element {
+        Element::Struct(_) => None,
+        Element::Enum(_) => Some("«enum»"),
+        Element::Trait(_) => Some("«trait»"),
+    }
+}
+
+/// One line per field (`+ name: Type`), variant (`Variant(A, B)`) or associated type (`type Key`)
+pub fn members(element: &Element) -> Vec<String> {
+    match element {
+        Element::Struct(struct_element) => struct_element
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(position, field)| {
+                format!(
+                    "{} {}: {}",
+                    field.vis.uml_marker(),
+                    field.label(position),
+                    field.ty
+                )
+            })
+            .collect(),
+        Element::Enum(enum_element) => enum_element.variants.iter().map(variant_line).collect(),
+        Element::Trait(trait_element) => trait_element
+            .associated_types
+            .iter()
+            .map(|name| format!("type {}", name))
+            .collect(),
+    }
+}
+
+fn variant_line(variant: &Variant) -> String {
+    if variant.fields.is_empty() {
+        return variant.name.clone();
+    }
+    let named = variant.fields.iter().any(|field| field.name.is_some());
+    let fields: Vec<String> = variant
+        .fields
+        .iter()
+        .enumerate()
+        .map(|(position, field)| match named {
+            true => format!("{}: {}", field.label(position), field.ty),
+            false => field.ty.to_string(),
+        })
+        .collect();
+    match named {
+        true => format!("{} {{ {} }}", variant.name, fields.join(", ")),
+        false => format!("{}({})", variant.name, fields.join(", ")),
+    }
+}
+
+/// Escapes text for a quoted DOT string
+fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Escapes text for a field of a `shape = record` label, where `{ } | < >` are structural
+fn escape_record(text: &str) -> String {
+    let mut escaped = String::new();
+    for c in escape(text).chars() {
+        if matches!(c, '{' | '}' | '|' | '<' | '>') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+pub(crate) fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::funcs::*;
+
+    const TEST_CODE: &str = r#"
+        /// Cached "values"
+        pub struct Cache<K> {
+            pub keys: Vec<K>,
+            pub(crate) stats: Stats,
+            dirty: bool,
+        }
+
+        pub enum Stats {
+            Empty,
+            Hits(u64),
+            Ratio { hits: u64, misses: u64 },
+        }
+    "#;
+
+    fn build() -> (Vec<Element>, DiGraph<String, Edge>, Vec<NodeIndex>) {
+        let elements = from_item_to_structs(filter_structs(parse_rust_code(TEST_CODE)));
+        let mut graph = DiGraph::<String, Edge>::new();
+        let node_indices = create_nodes_for_graph(&elements, &mut graph);
+        add_edges_for_graph(&elements, &mut graph, &node_indices);
+        (elements, graph, node_indices)
+    }
+
+    #[test]
+    fn test_to_dot() {
+        let (elements, graph, node_indices) = build();
+        let dot = to_dot(&elements, &graph, &node_indices, &ExportOptions::default());
+
+        assert_eq!(
+            dot,
+            "digraph {\n    \
+             0 [ label = \"Cache<K>\" tooltip = \"Cached \\\"values\\\"\" ]\n    \
+             1 [ label = \"Stats\" ]\n    \
+             0 -> 1 [ label = \"stats\" ]\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_members() {
+        let (elements, graph, node_indices) = build();
+
+        assert_eq!(
+            members(&elements[0]),
+            vec!["+ keys: Vec<K>", "~ stats: Stats", "- dirty: bool"]
+        );
+        assert_eq!(
+            members(&elements[1]),
+            vec!["Empty", "Hits(u64)", "Ratio { hits: u64, misses: u64 }"]
+        );
+
+        let options = ExportOptions {
+            members: true,
+            ..ExportOptions::default()
+        };
+        let dot = to_dot(&elements, &graph, &node_indices, &options);
+        assert!(dot.contains(
+            "shape = record label = \"{+ Cache\\<K\\>|+ keys: Vec\\<K\\>\\l~ stats: Stats\\l- dirty: bool\\l}\""
+        ));
+        assert!(dot.contains("label = \"{«enum»\\n+ Stats|Empty\\lHits(u64)\\lRatio \\{ hits: u64, misses: u64 \\}\\l}\""));
+    }
+
+    const MODULE_CODE: &str = "
+        pub struct App {
+            pub session: auth::Session,
+        }
+
+        pub mod auth {
+            pub struct Session {
+                pub user: model::User,
+            }
+
+            pub mod model {
+                pub enum User {}
+            }
+        }
+    ";
+
+    fn build_modules() -> (Vec<Element>, DiGraph<String, Edge>, Vec<NodeIndex>) {
+        let elements = collect_model(parse_rust_code(MODULE_CODE)).elements;
+        let mut graph = DiGraph::<String, Edge>::new();
+        let node_indices = create_nodes_for_graph(&elements, &mut graph);
+        add_edges_for_graph(&elements, &mut graph, &node_indices);
+        (elements, graph, node_indices)
+    }
+
+    #[test]
+    fn test_to_dot_clusters() {
+        let (elements, graph, node_indices) = build_modules();
+        let options = ExportOptions {
+            clusters: true,
+            ..ExportOptions::default()
+        };
+        let dot = to_dot(&elements, &graph, &node_indices, &options);
+
+        assert_eq!(
+            dot,
+            "digraph {\n    \
+             0 [ label = \"App\" ]\n    \
+             subgraph \"cluster_auth\" {\n        \
+             label = \"auth\"\n        \
+             1 [ label = \"Session\" ]\n        \
+             subgraph \"cluster_auth::model\" {\n            \
+             label = \"auth::model\"\n            \
+             2 [ label = \"User\" ]\n        \
+             }\n    \
+             }\n    \
+             0 -> 1 [ label = \"session\" ]\n    \
+             1 -> 2 [ label = \"user\" ]\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_to_dot_external_types() {
+        let code = "
+        use chrono::DateTime;
+
+        pub struct Event {
+            pub at: DateTime<Utc>,
+            pub name: String,
+        }
+            ";
+        let model = collect_model(parse_rust_code(code));
+        let mut graph = DiGraph::<String, Edge>::new();
+        let node_indices = create_nodes_for_graph(&model.elements, &mut graph);
+        let options = GraphOptions {
+            external_types: true,
+            external_crates: external_crates(&model),
+            ..GraphOptions::default()
+        };
+        add_edges_for_graph_with_options(&model.elements, &mut graph, &node_indices, &options);
+        let dot = to_dot(
+            &model.elements,
+            &graph,
+            &node_indices,
+            &ExportOptions::default(),
+        );
+
+        assert_eq!(
+            dot,
+            "digraph {\n    \
+             0 [ label = \"Event\" ]\n    \
+             2 [ label = \"Utc\" style = dashed color = gray50 fontcolor = gray50 ]\n    \
+             3 [ label = \"String\" style = dashed color = gray50 fontcolor = gray50 ]\n    \
+             subgraph \"cluster_crate_chrono\" {\n        \
+             label = \"chrono\"\n        \
+             style = dashed\n        \
+             1 [ label = \"DateTime\" style = dashed color = gray50 fontcolor = gray50 ]\n    \
+             }\n    \
+             0 -> 1 [ label = \"at\" ]\n    \
+             0 -> 2 [ label = \"at\" ]\n    \
+             0 -> 3 [ label = \"name\" ]\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_to_plantuml_and_mermaid() {
+        let (elements, graph, node_indices) = build_modules();
+        let options = ExportOptions {
+            members: true,
+            clusters: true,
+            ..ExportOptions::default()
+        };
+
+        assert_eq!(
+            to_plantuml(&elements, &graph, &node_indices, &options),
+            "@startuml\n\
+             class \"App\" as N0 {\n  + session: Session\n}\n\
+             package auth {\n  \
+             class \"Session\" as N1 {\n    + user: User\n  }\n  \
+             package model {\n    \
+             enum \"User\" as N2 {\n    }\n  \
+             }\n\
+             }\n\
+             N0 --> N1 : session\n\
+             N1 --> N2 : user\n\
+             @enduml\n"
+        );
+        assert_eq!(
+            to_mermaid(&elements, &graph, &node_indices, &options),
+            "classDiagram\n    \
+             class N0[\"App\"]\n    \
+             namespace auth {\n        \
+             class N1[\"Session\"]\n    \
+             }\n    \
+             namespace auth.model {\n        \
+             class N2[\"User\"]\n    \
+             }\n    \
+             N0 : + session: Session\n    \
+             N1 : + user: User\n    \
+             <<enumeration>> N2\n    \
+             N0 --> N1 : session\n    \
+             N1 --> N2 : user\n"
+        );
+    }
+}
@@ -0,0 +1,311 @@
+use crate::cfg::*;
+use crate::diff::*;
+use crate::structures::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// `[features]` table of a Cargo.toml: feature name to the features it enables
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct CargoFeatures {
+    pub features: BTreeMap<String, Vec<String>>,
+    /// `optional = true` entries of the (build and target) dependency tables, each with an
+    /// implicit feature of its name unless a feature enables it as `dep:name`
+    pub optional_dependencies: BTreeSet<String>,
+}
+
+impl CargoFeatures {
+    pub fn parse(manifest: &str) -> Result<Self, toml::de::Error> {
+        let manifest: toml::Table = toml::from_str(manifest)?;
+        let features = manifest
+            .get("features")
+            .and_then(|features| features.as_table())
+            .map(|table| {
+                table
+                    .iter()
+                    .map(|(name, enables)| {
+                        let enables = enables
+                            .as_array()
+                            .map(|enables| {
+                                enables
+                                    .iter()
+                                    .filter_map(|value| value.as_str().map(String::from))
+                                    .collect()
+                            })
+                            .unwrap_or_default();
+                        (name.clone(), enables)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut tables = vec![&manifest];
+        if let Some(targets) = manifest.get("target").and_then(|target| target.as_table()) {
+            tables.extend(targets.values().filter_map(|target| target.as_table()));
+        }
+        let optional_dependencies = tables
+            .into_iter()
+            .flat_map(|table| {
+                ["dependencies", "build-dependencies"]
+                    .into_iter()
+                    .filter_map(|key| table.get(key).and_then(|deps| deps.as_table()))
+            })
+            .flatten()
+            .filter(|(_, dependency)| {
+                dependency
+                    .get("optional")
+                    .and_then(|optional| optional.as_bool())
+                    == Some(true)
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        Ok(CargoFeatures {
+            features,
+            optional_dependencies,
+        })
+    }
+
+    pub fn read(manifest_path: &Path) -> io::Result<Self> {
+        let manifest = fs::read_to_string(manifest_path)?;
+        CargoFeatures::parse(&manifest)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))
+    }
+
+    /// `[features]` entry or implicit feature of an optional dependency
+    pub fn is_feature(&self, name: &str) -> bool {
+        let enabled_as_dep = || {
+            let dep = format!("dep:{}", name);
+            self.features
+                .values()
+                .flatten()
+                .any(|enables| *enables == dep)
+        };
+        self.features.contains_key(name)
+            || (self.optional_dependencies.contains(name) && !enabled_as_dep())
+    }
+
+    /// Every feature turned on by `requested`, following the `[features]` table.
+    /// `dep:name` and `crate/feature` entries enable nothing in this crate and are skipped,
+    /// other names (including implicit features of optional dependencies) are kept.
+    /// `excluded` is never enabled, even when another feature asks for it.
+    /// Fails on a requested name that is not a feature, `default` may be missing
+    pub fn resolve(
+        &self,
+        requested: &[&str],
+        excluded: Option<&str>,
+    ) -> Result<Vec<String>, String> {
+        if let Some(unknown) = requested
+            .iter()
+            .find(|&&name| name != "default" && !self.is_feature(name))
+        {
+            return Err(format!("unknown feature `{}`", unknown));
+        }
+        let mut enabled: Vec<String> = Vec::new();
+        let mut pending: Vec<String> = requested.iter().map(|name| name.to_string()).collect();
+        while let Some(name) = pending.pop() {
+            if name.starts_with("dep:")
+                || name.contains('/')
+                || Some(name.as_str()) == excluded
+                || enabled.contains(&name)
+            {
+                continue;
+            }
+            if let Some(enables) = self.features.get(&name) {
+                pending.extend(enables.iter().cloned());
+            }
+            enabled.push(name);
+        }
+        enabled.sort();
+        Ok(enabled)
+    }
+
+    /// Configurations without and with `feature`, on top of the default features (unless
+    /// `default_features` is false) and the features of `base`. The other options of `base`
+    /// (`--cfg`, `--no-test`) are kept in both, features stay unknown without it
+    pub fn configs(
+        &self,
+        feature: &str,
+        default_features: bool,
+        base: &CfgConfig,
+    ) -> Result<(CfgConfig, CfgConfig), String> {
+        let mut requested: Vec<&str> = if default_features {
+            vec!["default"]
+        } else {
+            Vec::new()
+        };
+        requested.extend(base.features.iter().flatten().map(String::as_str));
+        let mut with_requested = requested.clone();
+        with_requested.push(feature);
+
+        let without = CfgConfig {
+            features: Some(self.resolve(&requested, Some(feature))?),
+            ..base.clone()
+        };
+        let with = CfgConfig {
+            features: Some(self.resolve(&with_requested, None)?),
+            ..base.clone()
+        };
+        Ok((without, with))
+    }
+}
+
+/// Cargo.toml in the directory of `path` or the closest one above it
+pub fn find_manifest(path: &Path) -> Option<PathBuf> {
+    path.ancestors()
+        .map(|dir| dir.join("Cargo.toml"))
+        .find(|manifest| manifest.is_file())
+}
+
+/// What enabling `feature` adds to (or removes from) the model, in the configuration `base`
+/// (see `CargoFeatures::configs`). Fails when `feature` or a feature of `base` is unknown
+pub fn feature_diff(
+    model: &Model,
+    features: &CargoFeatures,
+    feature: &str,
+    default_features: bool,
+    base: &CfgConfig,
+) -> Result<ModelDiff, String> {
+    let (without, with) = features.configs(feature, default_features, base)?;
+    Ok(diff_models(
+        &apply_cfg(model, &without),
+        &apply_cfg(model, &with),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::funcs::*;
+
+    const MANIFEST: &str = r#"
+        [package]
+        name = "shop"
+
+        [features]
+        default = ["std"]
+        std = []
+        serde = ["dep:serde", "chrono/serde"]
+        async = ["tokio", "serde"]
+        ids = ["dep:uuid"]
+
+        [dependencies]
+        serde = { version = "1", optional = true }
+        tokio = { version = "1", optional = true }
+        uuid = { version = "1", optional = true }
+        chrono = "0.4"
+    "#;
+
+    #[test]
+    fn test_resolve() {
+        let features = CargoFeatures::parse(MANIFEST).unwrap();
+
+        assert_eq!(
+            features.resolve(&["default"], None).unwrap(),
+            vec!["default", "std"]
+        );
+        assert_eq!(
+            features.resolve(&["async"], None).unwrap(),
+            vec!["async", "serde", "tokio"]
+        );
+        assert_eq!(
+            features.resolve(&["async"], Some("serde")).unwrap(),
+            vec!["async", "tokio"]
+        );
+    }
+
+    #[test]
+    fn test_unknown_feature() {
+        let features = CargoFeatures::parse(MANIFEST).unwrap();
+
+        assert_eq!(features.resolve(&["tokio"], None).unwrap(), vec!["tokio"]);
+        assert_eq!(
+            features.resolve(&["serd"], None),
+            Err("unknown feature `serd`".to_string())
+        );
+        // `dep:uuid` hides the implicit feature, `chrono` is not optional
+        assert!(features.resolve(&["uuid"], None).is_err());
+        assert!(features.resolve(&["chrono"], None).is_err());
+
+        let model = collect_model(parse_rust_code("pub struct Order {}"));
+        let diff = feature_diff(&model, &features, "serd", true, &CfgConfig::default());
+        assert_eq!(diff, Err("unknown feature `serd`".to_string()));
+        let base = CfgConfig {
+            features: Some(vec!["asynk".to_string()]),
+            ..CfgConfig::default()
+        };
+        assert!(feature_diff(&model, &features, "serde", true, &base).is_err());
+    }
+
+    #[test]
+    fn test_feature_diff() {
+        let code = r#"
+            #[cfg_attr(feature = "serde", derive(Serialize))]
+            pub struct Order {
+                pub id: u64,
+                #[cfg(feature = "async")]
+                pub waker: Waker,
+            }
+
+            #[cfg(feature = "async")]
+            pub struct Waker {}
+
+            #[cfg(not(feature = "std"))]
+            pub struct NoStd {}
+        "#;
+        let model = collect_model(parse_rust_code(code));
+        let features = CargoFeatures::parse(MANIFEST).unwrap();
+        let diff = feature_diff(&model, &features, "async", true, &CfgConfig::default()).unwrap();
+
+        let elements: Vec<(&str, Change)> = diff
+            .elements
+            .iter()
+            .map(|e| (e.name.as_str(), e.change))
+            .collect();
+        assert_eq!(
+            elements,
+            vec![("Order", Change::Changed), ("Waker", Change::Added)]
+        );
+        let members: Vec<(&str, Change)> = diff.elements[0]
+            .members
+            .iter()
+            .map(|m| (m.name.as_str(), m.change))
+            .collect();
+        assert_eq!(
+            members,
+            vec![
+                ("#[derive(Serialize)]", Change::Added),
+                ("id", Change::Unchanged),
+                ("waker", Change::Added),
+            ]
+        );
+        assert_eq!(diff.edges.len(), 1);
+        assert_eq!(diff.edges[0].change, Change::Added);
+
+        let diff = feature_diff(&model, &features, "std", false, &CfgConfig::default()).unwrap();
+        let elements: Vec<(&str, Change)> = diff
+            .elements
+            .iter()
+            .map(|e| (e.name.as_str(), e.change))
+            .collect();
+        assert_eq!(
+            elements,
+            vec![("Order", Change::Unchanged), ("NoStd", Change::Removed)]
+        );
+
+        // `--features async` is on in both models, so enabling `serde` only adds the derive
+        let base = CfgConfig {
+            features: Some(vec!["async".to_string()]),
+            ..CfgConfig::default()
+        };
+        let diff = feature_diff(&model, &features, "serde", true, &base).unwrap();
+        let changed: Vec<&str> = diff
+            .elements
+            .iter()
+            .flat_map(|e| &e.members)
+            .filter(|m| m.change != Change::Unchanged)
+            .map(|m| m.name.as_str())
+            .collect();
+        assert_eq!(changed, vec!["#[derive(Serialize)]"]);
+    }
+}
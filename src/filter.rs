@@ -0,0 +1,177 @@
+use crate::structures::*;
+use regex::Regex;
+
+/// Which elements make it into the diagrams. An element is kept when it matches one of the
+/// `include` globs (or there are none) and none of the `exclude` regexes or modules
+#[derive(Debug, Default, Clone)]
+pub struct Filters {
+    /// Globs over the full path of the element (`crate::domain::*`), `*` matches anything
+    /// including `::`. The `crate::` prefix may be left out
+    pub include: Vec<String>,
+    /// Regexes searched in the element name and in its full path (`.*Dto$`)
+    pub exclude: Vec<Regex>,
+    /// Module paths (`tests`, `api::internal`) whose elements are dropped, wherever they
+    /// appear in the module tree
+    pub exclude_modules: Vec<String>,
+}
+
+impl Filters {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty() && self.exclude_modules.is_empty()
+    }
+
+    pub fn keeps(&self, element: &Element) -> bool {
+        let path = full_path(element.module(), element.name());
+        let included = self.include.is_empty()
+            || self.include.iter().any(|glob| {
+                glob_matches(glob, &path) || glob_matches(glob, &path["crate::".len()..])
+            });
+        let excluded = self
+            .exclude
+            .iter()
+            .any(|regex| regex.is_match(element.name()) || regex.is_match(&path));
+        included && !excluded && !self.in_excluded_module(element.module())
+    }
+
+    fn in_excluded_module(&self, module: &[String]) -> bool {
+        self.exclude_modules.iter().any(|excluded| {
+            let excluded: Vec<&str> = excluded.split("::").collect();
+            module
+                .windows(excluded.len())
+                .any(|window| window.iter().zip(&excluded).all(|(a, b)| a == b))
+        })
+    }
+}
+
+/// `crate::a::b::Name`
+pub(crate) fn full_path(module: &[String], name: &str) -> String {
+    let mut path = String::from("crate::");
+    for segment in module {
+        path.push_str(segment);
+        path.push_str("::");
+    }
+    path.push_str(name);
+    path
+}
+
+pub(crate) fn glob_matches(glob: &str, text: &str) -> bool {
+    let pattern: Vec<String> = glob.split('*').map(regex::escape).collect();
+    Regex::new(&format!("^{}$", pattern.join(".*")))
+        .map(|regex| regex.is_match(text))
+        .unwrap_or(false)
+}
+
+/// Drops the elements the filters don't keep, and the modules excluded by
+/// `exclude_modules`. Use the names of the dropped elements as `GraphOptions::placeholders`
+/// to keep edges to them
+pub fn filter_model(model: &Model, filters: &Filters) -> Model {
+    Model {
+        modules: model
+            .modules
+            .iter()
+            .filter(|module| !filters.in_excluded_module(&module.path))
+            .cloned()
+            .collect(),
+        elements: model
+            .elements
+            .iter()
+            .filter(|element| filters.keeps(element))
+            .cloned()
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::funcs::*;
+    use petgraph::graph::DiGraph;
+
+    const TEST_CODE: &str = "
+        pub struct App {
+            pub order: domain::Order,
+            pub dto: api::OrderDto,
+            pub dtos: Vec<api::OrderDto>,
+        }
+
+        pub mod domain {
+            pub struct Order {}
+
+            mod tests {
+                pub struct Fixture {}
+            }
+        }
+
+        pub mod api {
+            pub struct OrderDto {}
+        }
+    ";
+
+    fn names(model: &Model) -> Vec<&str> {
+        model.elements.iter().map(|e| e.name()).collect()
+    }
+
+    #[test]
+    fn test_filter_model() {
+        let model = collect_model(parse_rust_code(TEST_CODE));
+
+        let include = Filters {
+            include: vec!["crate::domain::*".to_string()],
+            ..Filters::default()
+        };
+        assert_eq!(
+            names(&filter_model(&model, &include)),
+            vec!["Order", "Fixture"]
+        );
+
+        let exclude = Filters {
+            exclude: vec![Regex::new(".*Dto$").unwrap()],
+            exclude_modules: vec!["tests".to_string()],
+            ..Filters::default()
+        };
+        let filtered = filter_model(&model, &exclude);
+        assert_eq!(names(&filtered), vec!["App", "Order"]);
+        assert_eq!(filtered.modules.len(), 3);
+
+        let include_short = Filters {
+            include: vec!["api::*".to_string(), "App".to_string()],
+            ..Filters::default()
+        };
+        assert_eq!(
+            names(&filter_model(&model, &include_short)),
+            vec!["App", "OrderDto"]
+        );
+    }
+
+    #[test]
+    fn test_filtered_edges() {
+        let model = collect_model(parse_rust_code(TEST_CODE));
+        let filters = Filters {
+            exclude: vec![Regex::new("Dto$").unwrap()],
+            ..Filters::default()
+        };
+        let filtered = filter_model(&model, &filters);
+
+        let mut graph = DiGraph::<String, Edge>::new();
+        let node_indices = create_nodes_for_graph(&filtered.elements, &mut graph);
+        add_edges_for_graph(&filtered.elements, &mut graph, &node_indices);
+        assert_eq!(graph.edge_count(), 1);
+
+        let options = GraphOptions {
+            placeholders: model
+                .elements
+                .iter()
+                .filter(|element| !filters.keeps(element))
+                .map(|element| element.name().to_string())
+                .collect(),
+            ..GraphOptions::default()
+        };
+        let mut graph = DiGraph::<String, Edge>::new();
+        let node_indices = create_nodes_for_graph(&filtered.elements, &mut graph);
+        add_edges_for_graph_with_options(&filtered.elements, &mut graph, &node_indices, &options);
+        assert_eq!(graph.node_count(), 4);
+        assert_eq!(graph[petgraph::graph::NodeIndex::new(3)], "external");
+        assert_eq!(graph.edge_count(), 3);
+        assert_eq!(graph.neighbors(node_indices[0]).count(), 3);
+    }
+}
@@ -0,0 +1,178 @@
+use crate::structures::*;
+use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::Direction as EdgeDirection;
+use std::collections::HashSet;
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum Direction {
+    /// What the type contains
+    Out,
+    /// What contains the type
+    In,
+    #[default]
+    Both,
+}
+
+/// Elements with their graph and node indices, as `to_dot` takes them
+pub type ElementGraph = (Vec<Element>, DiGraph<String, Edge>, Vec<NodeIndex>);
+
+/// Neighborhood of `type_name` (the element name) to `depth` hops
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Focus {
+    pub type_name: String,
+    pub depth: usize,
+    pub direction: Direction,
+}
+
+/// Subgraph of the nodes within `focus.depth` edges of the focused type, following edges in
+/// `focus.direction`, with every edge between those nodes. Elements and node indices are
+/// filtered to match, as `to_dot` expects. `None` when no element has that name
+pub fn focus_graph(
+    elements: &[Element],
+    graph: &DiGraph<String, Edge>,
+    node_indices: &[NodeIndex],
+    focus: &Focus,
+) -> Option<ElementGraph> {
+    let position = elements
+        .iter()
+        .position(|element| element.name() == focus.type_name)?;
+    let start = node_indices[position];
+
+    let directions: &[EdgeDirection] = match focus.direction {
+        Direction::Out => &[EdgeDirection::Outgoing],
+        Direction::In => &[EdgeDirection::Incoming],
+        Direction::Both => &[EdgeDirection::Outgoing, EdgeDirection::Incoming],
+    };
+    let mut kept = HashSet::from([start]);
+    let mut frontier = vec![start];
+    for _ in 0..focus.depth {
+        let mut next = Vec::new();
+        for node in frontier {
+            for &direction in directions {
+                for neighbor in graph.neighbors_directed(node, direction) {
+                    if kept.insert(neighbor) {
+                        next.push(neighbor);
+                    }
+                }
+            }
+        }
+        frontier = next;
+    }
+
+    // filter_map keeps the order of the remaining nodes, so a kept node's new index is the
+    // number of kept nodes before it
+    let new_index = |node: NodeIndex| {
+        NodeIndex::new(
+            graph
+                .node_indices()
+                .take_while(|&i| i != node)
+                .filter(|i| kept.contains(i))
+                .count(),
+        )
+    };
+    let focused = graph.filter_map(
+        |node, label| kept.contains(&node).then(|| label.clone()),
+        |_, edge| Some(edge.clone()),
+    );
+    let (focused_elements, focused_indices) = elements
+        .iter()
+        .zip(node_indices)
+        .filter(|(_, node)| kept.contains(node))
+        .map(|(element, &node)| (element.clone(), new_index(node)))
+        .unzip();
+    Some((focused_elements, focused, focused_indices))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::funcs::*;
+
+    const TEST_CODE: &str = "
+        pub struct Shop {
+            pub orders: Vec<Order>,
+        }
+
+        pub struct Order {
+            pub total: Money,
+            pub customer: Customer,
+        }
+
+        pub struct Customer {
+            pub address: Address,
+        }
+
+        pub struct Money {}
+        pub struct Address {}
+        pub struct Unrelated {}
+    ";
+
+    fn focus(type_name: &str, depth: usize, direction: Direction) -> Vec<String> {
+        let elements = from_item_to_structs(filter_structs(parse_rust_code(TEST_CODE)));
+        let mut graph = DiGraph::<String, Edge>::new();
+        let node_indices = create_nodes_for_graph(&elements, &mut graph);
+        add_edges_for_graph(&elements, &mut graph, &node_indices);
+        let focus = Focus {
+            type_name: type_name.to_string(),
+            depth,
+            direction,
+        };
+        let (elements, graph, node_indices) =
+            focus_graph(&elements, &graph, &node_indices, &focus).unwrap();
+
+        assert_eq!(elements.len(), node_indices.len());
+        for (element, node) in elements.iter().zip(&node_indices) {
+            assert_eq!(graph[*node], element.label());
+        }
+        elements.iter().map(|e| e.name().to_string()).collect()
+    }
+
+    #[test]
+    fn test_focus_directions() {
+        assert_eq!(
+            focus("Order", 1, Direction::Out),
+            vec!["Order", "Customer", "Money"]
+        );
+        assert_eq!(focus("Order", 1, Direction::In), vec!["Shop", "Order"]);
+        assert_eq!(
+            focus("Order", 2, Direction::Out),
+            vec!["Order", "Customer", "Money", "Address"]
+        );
+        assert_eq!(
+            focus("Money", 2, Direction::In),
+            vec!["Shop", "Order", "Money"]
+        );
+        assert_eq!(
+            focus("Customer", 1, Direction::Both),
+            vec!["Order", "Customer", "Address"]
+        );
+    }
+
+    #[test]
+    fn test_focus_edges() {
+        let elements = from_item_to_structs(filter_structs(parse_rust_code(TEST_CODE)));
+        let mut graph = DiGraph::<String, Edge>::new();
+        let node_indices = create_nodes_for_graph(&elements, &mut graph);
+        add_edges_for_graph(&elements, &mut graph, &node_indices);
+        let focus = Focus {
+            type_name: "Customer".to_string(),
+            depth: 1,
+            direction: Direction::In,
+        };
+        let (_, focused, focused_indices) =
+            focus_graph(&elements, &graph, &node_indices, &focus).unwrap();
+
+        assert_eq!(focused.node_count(), 2);
+        assert_eq!(focused.edge_count(), 1);
+        let edge = focused
+            .find_edge(focused_indices[0], focused_indices[1])
+            .unwrap();
+        assert_eq!(focused[edge].field, Some("customer".to_string()));
+
+        let missing = Focus {
+            type_name: "Missing".to_string(),
+            ..focus
+        };
+        assert!(focus_graph(&elements, &graph, &node_indices, &missing).is_none());
+    }
+}
@@ -1,5 +1,9 @@
+use crate::impls::uses_from_item;
 use crate::structures::*;
 use petgraph::graph::{DiGraph, NodeIndex};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
 use syn::{File, Item};
 
 pub fn parse_rust_code(code: &str) -> File {
@@ -15,6 +19,16 @@ pub fn filter_structs(parsed: File) -> Vec<Item> {
         .collect()
 }
 
+/// Structs, enums and traits: every item that becomes an element
+pub fn filter_elements(parsed: File) -> Vec<Item> {
+    parsed
+        .items
+        .into_iter()
+        .filter(|item| matches!(item, Item::Struct(_) | Item::Enum(_) | Item::Trait(_)))
+        .collect()
+}
+
+#[allow(clippy::single_match)]
 pub fn from_item_to_structs(input: Vec<Item>) -> Vec<Element> {
     let mut output = Vec::new();
     for i in input {
@@ -27,107 +41,623 @@ pub fn from_item_to_structs(input: Vec<Item>) -> Vec<Element> {
     output
 }
 
+/// Collects elements of the file and of its inline modules (`mod a { ... }`).
+/// Out-of-line modules (`mod a;`) are only followed by `load_model`
+pub fn collect_model(parsed: File) -> Model {
+    let mut model = Model::default();
+    let root = root_module();
+    collect_module(parsed.items, root, None, &mut model);
+    model
+}
+
+/// Reads the file and the files of its `mod a;` declarations (`a.rs` or `a/mod.rs`),
+/// as the compiler does starting from `lib.rs` or `main.rs`. Missing module files are skipped
+pub fn load_model(path: &Path) -> io::Result<Model> {
+    let parsed = read_rust_file(path)?;
+    let mut model = Model::default();
+    let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+    collect_module(parsed.items, root_module(), Some(dir), &mut model);
+    Ok(model)
+}
+
+/// Root file of the crate in `dir`: `src/lib.rs`, `src/main.rs`, `lib.rs` or `main.rs`
+pub fn crate_root(dir: &Path) -> Option<PathBuf> {
+    ["src/lib.rs", "src/main.rs", "lib.rs", "main.rs"]
+        .iter()
+        .map(|root| dir.join(root))
+        .find(|root| root.is_file())
+}
+
+/// The file and every file `load_model` reaches through `mod a;`, for analyses that need more
+/// than the type definitions (function bodies, spans)
+pub fn load_files(path: &Path) -> io::Result<Vec<(PathBuf, File)>> {
+    let parsed = read_rust_file(path)?;
+    let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+    let mut files = Vec::new();
+    collect_files(path.to_path_buf(), parsed, &dir, &mut files);
+    Ok(files)
+}
+
+fn collect_files(path: PathBuf, parsed: File, dir: &Path, files: &mut Vec<(PathBuf, File)>) {
+    let mut module_files = Vec::new();
+    out_of_line_modules(&parsed.items, dir, &mut module_files);
+    files.push((path, parsed));
+    for (file_path, child_dir) in module_files {
+        if let Ok(parsed) = read_rust_file(&file_path) {
+            collect_files(file_path, parsed, &child_dir, files);
+        }
+    }
+}
+
+/// Files of the `mod a;` declarations in `items` and their inline modules, with the directory
+/// their own submodules live in
+pub(crate) fn out_of_line_modules(
+    items: &[Item],
+    dir: &Path,
+    module_files: &mut Vec<(PathBuf, PathBuf)>,
+) {
+    for item in items {
+        let Item::Mod(item_mod) = item else {
+            continue;
+        };
+        let name = item_mod.ident.to_string();
+        match &item_mod.content {
+            Some((_, items)) => out_of_line_modules(items, &dir.join(&name), module_files),
+            None => {
+                if let Some(file_path) = module_file(dir, &name) {
+                    module_files.push((file_path, dir.join(&name)));
+                }
+            }
+        }
+    }
+}
+
+/// `dir/name.rs`, or `dir/name/mod.rs` when that does not exist
+fn module_file(dir: &Path, name: &str) -> Option<PathBuf> {
+    [
+        dir.join(format!("{}.rs", name)),
+        dir.join(name).join("mod.rs"),
+    ]
+    .into_iter()
+    .find(|path| path.is_file())
+}
+
+fn read_rust_file(path: &Path) -> io::Result<File> {
+    let source_code = fs::read_to_string(path)?;
+    syn::parse_file(&source_code).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {}", path.display(), err),
+        )
+    })
+}
+
+fn root_module() -> Module {
+    Module {
+        path: Vec::new(),
+        vis: Visibility::Public,
+        attrs: Attributes::default(),
+        uses: Vec::new(),
+    }
+}
+
+/// `dir` is where files of child modules are looked up, `None` when not loading from disk
+fn collect_module(items: Vec<Item>, mut module: Module, dir: Option<PathBuf>, model: &mut Model) {
+    let mut children = Vec::new();
+    for item in items {
+        match item {
+            Item::Use(item_use) => module.uses.extend(uses_from_item(&item_use)),
+            Item::Mod(item_mod) => children.push(item_mod),
+            item => {
+                if let Ok(mut element) = Element::try_from(item) {
+                    element.set_module(module.path.clone());
+                    model.elements.push(element);
+                }
+            }
+        }
+    }
+    let path = module.path.clone();
+    model.modules.push(module);
+
+    for item_mod in children {
+        let name = item_mod.ident.to_string();
+        let mut child_path = path.clone();
+        child_path.push(name.clone());
+        let child = Module {
+            path: child_path,
+            vis: Visibility::from(item_mod.vis),
+            attrs: Attributes::from(item_mod.attrs),
+            uses: Vec::new(),
+        };
+        match (item_mod.content, &dir) {
+            (Some((_, items)), dir) => {
+                let child_dir = dir.as_ref().map(|dir| dir.join(&name));
+                collect_module(items, child, child_dir, model);
+            }
+            (None, Some(dir)) => {
+                let parsed = module_file(dir, &name).and_then(|path| read_rust_file(&path).ok());
+                if let Some(parsed) = parsed {
+                    collect_module(parsed.items, child, Some(dir.join(&name)), model);
+                }
+            }
+            (None, None) => model.modules.push(child),
+        }
+    }
+}
+
+/// Nodes are labelled with `Element::label`, so generic elements are shown as `Cache<K: Hash, V>`
 pub fn create_nodes_for_graph(
     structs: &[Element],
-    graph: &mut DiGraph<String, ()>,
+    graph: &mut DiGraph<String, Edge>,
 ) -> Vec<NodeIndex> {
     structs
         .iter()
-        .map(|element| match element {
-            Element::Struct(struct_element) => graph.add_node(struct_element.name.clone()),
-            Element::Enum(enum_element) => graph.add_node(enum_element.name.clone()),
-        })
+        .map(|element| graph.add_node(element.label()))
         .collect()
 }
 
-fn add_edge_for_simple_type(
-    graph: &mut DiGraph<String, ()>,
-    node_indices: &[NodeIndex],
-    field: &Field,
-    field_type: &String,
-    element_name: &String,
-) {
-    if let Some(target_index) = node_indices.iter().position(|&i| graph[i] == *field_type) {
-        let source_index = node_indices
+#[derive(Debug, Default, Clone)]
+pub struct GraphOptions {
+    /// Add a node for every instantiation of a local generic type (`Wrapper<Inner>`)
+    /// and point the field at it, instead of linking the field straight to `Wrapper`
+    pub synthesize_bindings: bool,
+    /// Names of types filtered out of the model (see `filter_model`). Fields referring to them
+    /// point at a single `external` placeholder node instead of losing their edge
+    pub placeholders: Vec<String>,
+    /// Leaf node for every type that is neither local nor a standard container
+    /// (`DateTime`, `Uuid`, `String`), instead of leaving the field without an edge
+    pub external_types: bool,
+    /// Leaf node for primitive types (`u64`, `bool`, `str`)
+    pub primitive_types: bool,
+    /// Crate of external types, as `(type name, crate)` from `external_crates`.
+    /// Leaf nodes of these types are labelled `crate::Type`, which `to_dot` groups by crate
+    pub external_crates: Vec<(String, String)>,
+    /// Modules of the model (`Model::modules`). Their `use` items tell which of several local
+    /// types with the same name a field refers to, without them such fields get no edge
+    pub modules: Vec<Module>,
+}
+
+/// Standard containers and smart pointers, fields are always linked through them
+const STD_WRAPPERS: &[&str] = &[
+    "Option",
+    "Result",
+    "Box",
+    "Rc",
+    "Arc",
+    "Weak",
+    "Cell",
+    "RefCell",
+    "Mutex",
+    "RwLock",
+    "Cow",
+    "Pin",
+    "PhantomData",
+    "VecDeque",
+    "LinkedList",
+    "BinaryHeap",
+    "HashMap",
+    "HashSet",
+    "BTreeMap",
+    "BTreeSet",
+];
+
+const PRIMITIVES: &[&str] = &[
+    "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32", "i64",
+    "i128", "isize", "f32", "f64",
+];
+
+/// Crates of the types imported with `use` from outside the crate (`use chrono::DateTime`
+/// gives `("DateTime", "chrono")`) or written with a crate prefix in a field (`uuid::Uuid`),
+/// for `GraphOptions::external_crates`
+pub fn external_crates(model: &Model) -> Vec<(String, String)> {
+    let is_local = |module: &[String], first: &String| {
+        let mut child = module.to_vec();
+        child.push(first.clone());
+        matches!(first.as_str(), "crate" | "self" | "super")
+            || model.modules.iter().any(|m| m.path == [first.clone()])
+            || model.modules.iter().any(|m| m.path == child)
+    };
+    let mut crates: Vec<(String, String)> = Vec::new();
+    let mut add = |path: &[String], module: &[String]| {
+        let (Some(first), Some(last)) = (path.first(), path.last()) else {
+            return;
+        };
+        // `T::Item` and `Self::Item` are associated types
+        let associated = first.starts_with(|c: char| c.is_uppercase());
+        if is_local(module, first) || associated || path.len() < 2 || last == "*" {
+            return;
+        }
+        if !crates.iter().any(|(name, _)| name == last) {
+            crates.push((last.clone(), first.clone()));
+        }
+    };
+    for module in &model.modules {
+        for used in &module.uses {
+            add(&used.path, &module.path);
+        }
+    }
+    for element in &model.elements {
+        let fields: Vec<&Field> = match element {
+            Element::Struct(struct_element) => struct_element.fields.iter().collect(),
+            Element::Enum(enum_element) => enum_element
+                .variants
+                .iter()
+                .flat_map(|variant| &variant.fields)
+                .collect(),
+            Element::Trait(_) => Vec::new(),
+        };
+        for path in fields.iter().flat_map(|field| &field.paths) {
+            // `use crate::db;` makes `db::Row` local
+            let imported_module = model
+                .modules
+                .iter()
+                .filter(|m| m.path == element.module())
+                .flat_map(|m| &m.uses)
+                .any(|used| {
+                    used.path.last() == path.first()
+                        && used.path.first().is_some_and(|first| is_local(&[], first))
+                });
+            if !imported_module {
+                add(path, element.module());
+            }
+        }
+    }
+    crates
+}
+
+const PLACEHOLDER_LABEL: &str = "external";
+
+/// Node labels include generics, so lookup goes through element names.
+/// `node_indices` is parallel to `elements`, as returned by `create_nodes_for_graph`
+struct GraphBuilder<'a> {
+    graph: &'a mut DiGraph<String, Edge>,
+    elements: &'a [Element],
+    node_indices: &'a [NodeIndex],
+    options: &'a GraphOptions,
+    /// Index of the element whose fields are being walked, for its generic bounds
+    owner: usize,
+    /// `Field::paths` of the field being walked
+    paths: &'a [Vec<String>],
+}
+
+impl GraphBuilder<'_> {
+    /// Local element the owner's field refers to by `type_name`. With several elements of that
+    /// name, the one at the path written in the field, imported by `use`, or declared in the
+    /// owner's module; `None` when it stays ambiguous or the path leads out of the crate
+    fn node_for(&self, type_name: &str) -> Option<NodeIndex> {
+        let candidates: Vec<usize> = (0..self.elements.len())
+            .filter(|&index| self.elements[index].name() == type_name)
+            .collect();
+        let in_module = |module: &[String]| {
+            candidates
+                .iter()
+                .copied()
+                .find(|&index| self.elements[index].module() == module)
+        };
+        let module = self.elements[self.owner].module();
+        let uses = self
+            .options
+            .modules
             .iter()
-            .position(|&i| graph[i] == *element_name)
-            .unwrap();
-        graph.add_edge(node_indices[source_index], node_indices[target_index], ());
+            .find(|m| m.path == module)
+            .map(|m| m.uses.as_slice())
+            .unwrap_or_default();
+
+        // A path into the crate that doesn't lead to the type may go through a re-export
+        let only = match candidates.as_slice() {
+            [only] => Some(*only),
+            _ => None,
+        };
+        let ends_with = |path: &[String], name: &str| path.last().map(String::as_str) == Some(name);
+        let written = self.paths.iter().find(|path| ends_with(path, type_name));
+        let imported = uses.iter().find(|used| ends_with(&used.path, type_name));
+        let found = match (written, imported) {
+            (Some(path), _) => self
+                .module_of(module, &path[..path.len() - 1], uses)
+                .and_then(|target| in_module(&target).or(only)),
+            (None, Some(used)) => self
+                .module_of(module, &used.path[..used.path.len() - 1], &[])
+                .and_then(|target| in_module(&target).or(only)),
+            (None, None) => in_module(module)
+                .or_else(|| {
+                    uses.iter()
+                        .filter(|used| ends_with(&used.path, "*"))
+                        .filter_map(|used| {
+                            self.module_of(module, &used.path[..used.path.len() - 1], &[])
+                        })
+                        .find_map(|target| in_module(&target))
+                })
+                .or(only),
+        };
+        found.map(|index| self.node_indices[index])
+    }
+
+    /// Module a path prefix written in `module` leads to: `crate::`, `self::`, `super::`, a
+    /// child module, a module imported by one of `uses`, or a module of the crate root.
+    /// `None` for other crates
+    fn module_of(&self, module: &[String], prefix: &[String], uses: &[Use]) -> Option<Vec<String>> {
+        let (first, rest) = prefix.split_first()?;
+        match first.as_str() {
+            "crate" => return Some(rest.to_vec()),
+            "self" => return Some([module, rest].concat()),
+            "super" => {
+                let parent = &module[..module.len().checked_sub(1)?];
+                return match rest {
+                    [] => Some(parent.to_vec()),
+                    _ => self.module_of(parent, rest, &[]),
+                };
+            }
+            _ => {}
+        }
+        let child = [module, prefix].concat();
+        if self.is_module(&child[..module.len() + 1]) {
+            return Some(child);
+        }
+        if let Some(used) = uses.iter().find(|used| used.path.last() == Some(first)) {
+            let target = self.module_of(module, &used.path, &[])?;
+            return Some([target.as_slice(), rest].concat());
+        }
+        self.is_module(&prefix[..1]).then(|| prefix.to_vec())
+    }
+
+    /// A module of `GraphOptions::modules`, or of an element when they are not given
+    fn is_module(&self, path: &[String]) -> bool {
+        self.options.modules.iter().any(|m| m.path == path)
+            || self.elements.iter().any(|e| e.module().starts_with(path))
+    }
+
+    /// Local trait that declares the associated type `name`. Without an explicit trait
+    /// (`T::Item`) the bounds of the owner's type parameter `T` are searched
+    fn trait_node_for(
+        &self,
+        self_ty: &Type,
+        trait_name: &Option<String>,
+        name: &str,
+    ) -> Option<NodeIndex> {
+        let candidates: Vec<&str> = match (trait_name, self_ty) {
+            (Some(trait_name), _) => vec![trait_name.as_str()],
+            (None, Type::TypeParam(param)) => {
+                let generics = self.elements[self.owner].generics();
+                let param_bounds = generics.params.iter().filter_map(|p| match p {
+                    GenericParam::Type {
+                        name: param_name,
+                        bounds,
+                        ..
+                    } if param_name == param => Some(bounds),
+                    _ => None,
+                });
+                let where_bounds = generics
+                    .where_clause
+                    .iter()
+                    .filter(|predicate| &predicate.bounded == param)
+                    .map(|predicate| &predicate.bounds);
+                param_bounds
+                    .chain(where_bounds)
+                    .flatten()
+                    .map(|bound| bound_trait_name(bound))
+                    .collect()
+            }
+            _ => Vec::new(),
+        };
+
+        self.elements
+            .iter()
+            .position(|element| match element {
+                Element::Trait(trait_element) => {
+                    candidates.contains(&trait_element.name.as_str())
+                        && trait_element.associated_types.iter().any(|t| t == name)
+                }
+                _ => false,
+            })
+            .map(|index| self.node_indices[index])
+    }
+
+    fn add_edges_for_type(
+        &mut self,
+        source: NodeIndex,
+        field: &Option<String>,
+        field_type: &Type,
+        kind: EdgeKind,
+    ) {
+        match field_type {
+            Type::Simple(type_name) => {
+                if let Some(target) = self
+                    .node_for(type_name)
+                    .or_else(|| self.placeholder_for(type_name))
+                    .or_else(|| self.leaf_node_for(type_name))
+                {
+                    self.add_edge(source, target, kind, field);
+                }
+            }
+            Type::Vec(inner_type)
+            | Type::Other(inner_type)
+            | Type::Pointer {
+                inner: inner_type, ..
+            } => self.add_edges_for_type(source, field, inner_type, kind),
+            // Bounds of `dyn`/`impl` point at the trait itself when it is local
+            Type::Tuple(types) | Type::TraitObject(types) | Type::ImplTrait(types) => {
+                for ty in types {
+                    self.add_edges_for_type(source, field, ty, kind);
+                }
+            }
+            Type::FnPointer { inputs, output, .. } => {
+                for ty in inputs.iter().chain(output.as_deref()) {
+                    self.add_edges_for_type(source, field, ty, kind);
+                }
+            }
+            Type::Associated {
+                self_ty,
+                trait_name,
+                name,
+            } => {
+                if let Some(target) = self.trait_node_for(self_ty, trait_name, name) {
+                    self.add_edge(source, target, EdgeKind::AssociatedType, field);
+                }
+            }
+            Type::Generic(type_name, args) => match self.node_for(type_name) {
+                Some(template) if self.options.synthesize_bindings => {
+                    let bound = self.bound_node(template, field_type, args);
+                    self.add_edge(source, bound, kind, field);
+                }
+                Some(template) => {
+                    self.add_edge(source, template, kind, field);
+                    for arg in args {
+                        self.add_edges_for_type(source, field, arg, EdgeKind::BoundWith);
+                    }
+                }
+                None if self.options.placeholders.contains(type_name) => {
+                    if let Some(placeholder) = self.placeholder_for(type_name) {
+                        self.add_edge(source, placeholder, kind, field);
+                    }
+                }
+                None if !STD_WRAPPERS.contains(&type_name.as_str()) => {
+                    if let Some(leaf) = self.leaf_node_for(type_name) {
+                        self.add_edge(source, leaf, kind, field);
+                    }
+                    for arg in args {
+                        self.add_edges_for_type(source, field, arg, kind);
+                    }
+                }
+                // Not a local type (`Option`, `Box`, `HashMap`): look through it
+                None => {
+                    for arg in args {
+                        self.add_edges_for_type(source, field, arg, kind);
+                    }
+                }
+            },
+            Type::TypeParam(_) | Type::Literal(_) => {}
+        }
+    }
+
+    /// The `external` node standing for filtered-out types, created on first use
+    fn placeholder_for(&mut self, type_name: &str) -> Option<NodeIndex> {
+        if !self
+            .options
+            .placeholders
+            .iter()
+            .any(|name| name == type_name)
+        {
+            return None;
+        }
+        let existing = self
+            .graph
+            .node_indices()
+            .find(|&i| !self.node_indices.contains(&i) && self.graph[i] == PLACEHOLDER_LABEL);
+        Some(existing.unwrap_or_else(|| self.graph.add_node(PLACEHOLDER_LABEL.to_string())))
+    }
+
+    /// Leaf node of an external or primitive type, when enabled, created on first use
+    fn leaf_node_for(&mut self, type_name: &str) -> Option<NodeIndex> {
+        let enabled = match PRIMITIVES.contains(&type_name) {
+            true => self.options.primitive_types,
+            false => self.options.external_types,
+        };
+        if !enabled {
+            return None;
+        }
+        let label = match self
+            .options
+            .external_crates
+            .iter()
+            .find(|(name, _)| name == type_name)
+        {
+            Some((_, krate)) => format!("{}::{}", krate, type_name),
+            None => type_name.to_string(),
+        };
+        let existing = self
+            .graph
+            .node_indices()
+            .find(|&i| !self.node_indices.contains(&i) && self.graph[i] == label);
+        Some(existing.unwrap_or_else(|| self.graph.add_node(label)))
+    }
+
+    /// Node for `Wrapper<Inner>`, created together with its binding edges on first use
+    fn bound_node(&mut self, template: NodeIndex, bound_type: &Type, args: &[Type]) -> NodeIndex {
+        let label = bound_type.to_string();
+        if let Some(existing) = self
+            .graph
+            .node_indices()
+            .find(|&i| !self.node_indices.contains(&i) && self.graph[i] == label)
+        {
+            return existing;
+        }
+
+        let bound = self.graph.add_node(label);
+        self.add_edge(bound, template, EdgeKind::Binding, &None);
+        for arg in args {
+            self.add_edges_for_type(bound, &None, arg, EdgeKind::BoundWith);
+        }
+        bound
     }
+
+    fn add_edge(
+        &mut self,
+        source: NodeIndex,
+        target: NodeIndex,
+        kind: EdgeKind,
+        field: &Option<String>,
+    ) {
+        let field = field.clone();
+        self.graph.add_edge(source, target, Edge { kind, field });
+    }
+}
+
+/// Trait name of a bound: `Storage<u8>` and `crate::Storage` are both `Storage`
+fn bound_trait_name(bound: &str) -> &str {
+    let path = bound.split('<').next().unwrap_or(bound);
+    path.rsplit("::").next().unwrap_or(path).trim()
 }
 
-fn add_edge_for_vec_type(
-    graph: &mut DiGraph<String, ()>,
+pub fn add_edges_for_graph(
+    elements: &[Element],
+    graph: &mut DiGraph<String, Edge>,
     node_indices: &[NodeIndex],
-    field: &Field,
-    field_type: &Type,
-    element_name: &String,
 ) {
-    if let Type::Simple(inner_type) = field_type {
-        add_edge_for_simple_type(graph, node_indices, field, inner_type, element_name);
-    }
-    // Add other cases for Other type when needed
+    add_edges_for_graph_with_options(elements, graph, node_indices, &GraphOptions::default());
 }
 
-pub fn add_edges_for_graph(
-    elements: &Vec<Element>,
-    graph: &mut DiGraph<String, ()>,
+pub fn add_edges_for_graph_with_options(
+    elements: &[Element],
+    graph: &mut DiGraph<String, Edge>,
     node_indices: &[NodeIndex],
+    options: &GraphOptions,
 ) {
-    for element in elements {
+    let mut builder = GraphBuilder {
+        graph,
+        elements,
+        node_indices,
+        options,
+        owner: 0,
+        paths: &[],
+    };
+    for (owner, (element, &source)) in elements.iter().zip(node_indices).enumerate() {
+        builder.owner = owner;
         match element {
             Element::Struct(struct_element) => {
-                for field in &struct_element.fields {
-                    match &field.ty {
-                        Type::Simple(field_type) => {
-                            add_edge_for_simple_type(
-                                graph,
-                                node_indices,
-                                field,
-                                field_type,
-                                &struct_element.name,
-                            );
-                        }
-                        Type::Vec(field_type) => {
-                            add_edge_for_vec_type(
-                                graph,
-                                node_indices,
-                                field,
-                                field_type,
-                                &struct_element.name,
-                            );
-                        }
-                        _ => {} // Handle other cases when needed
-                    }
+                for (position, field) in struct_element.fields.iter().enumerate() {
+                    let label = field.label(position);
+                    builder.paths = &field.paths;
+                    builder.add_edges_for_type(source, &Some(label), &field.ty, EdgeKind::Field);
                 }
             }
             Element::Enum(enum_element) => {
                 for variant in &enum_element.variants {
-                    for field in &variant.fields {
-                        match &field.ty {
-                            Type::Simple(field_type) => {
-                                add_edge_for_simple_type(
-                                    graph,
-                                    node_indices,
-                                    field,
-                                    field_type,
-                                    &enum_element.name,
-                                );
-                            }
-                            Type::Vec(field_type) => {
-                                add_edge_for_vec_type(
-                                    graph,
-                                    node_indices,
-                                    field,
-                                    field_type,
-                                    &enum_element.name,
-                                );
-                            }
-                            _ => {} // Handle other cases when needed
-                        }
+                    for (position, field) in variant.fields.iter().enumerate() {
+                        let label = match &field.name {
+                            Some(name) => format!("{}.{}", variant.name, name),
+                            None => format!("{}.{}", variant.name, position),
+                        };
+                        builder.paths = &field.paths;
+                        builder.add_edges_for_type(
+                            source,
+                            &Some(label),
+                            &field.ty,
+                            EdgeKind::Field,
+                        );
                     }
                 }
             }
+            Element::Trait(_) => {}
         }
     }
 }
@@ -179,6 +709,10 @@ mod tests {
         let parsed = parse_rust_code(TEST_CODE);
         let structs = filter_structs(parsed);
         assert_eq!(structs.len(), 7);
+
+        let code = "pub struct A {} pub enum B {} pub trait C {}";
+        assert_eq!(filter_structs(parse_rust_code(code)).len(), 2);
+        assert_eq!(filter_elements(parse_rust_code(code)).len(), 3);
     }
 
     #[test]
@@ -187,7 +721,7 @@ mod tests {
         let items = filter_structs(parsed);
         let elements = from_item_to_structs(items);
 
-        let mut graph = DiGraph::<String, ()>::new();
+        let mut graph = DiGraph::<String, Edge>::new();
         let node_indices = create_nodes_for_graph(&elements, &mut graph);
 
         assert_eq!(graph.node_count(), 7);
@@ -201,7 +735,7 @@ mod tests {
         let items = filter_structs(parsed);
         let elements = from_item_to_structs(items);
 
-        let mut graph = DiGraph::<String, ()>::new();
+        let mut graph = DiGraph::<String, Edge>::new();
         let node_indices = create_nodes_for_graph(&elements, &mut graph);
 
         add_edges_for_graph(&elements, &mut graph, &node_indices);
@@ -228,7 +762,7 @@ mod tests {
         let items = filter_structs(parsed);
         let elements = from_item_to_structs(items);
 
-        let mut graph = DiGraph::<String, ()>::new();
+        let mut graph = DiGraph::<String, Edge>::new();
         let node_indices = create_nodes_for_graph(&elements, &mut graph);
 
         add_edges_for_graph(&elements, &mut graph, &node_indices);
@@ -236,4 +770,348 @@ mod tests {
         assert_eq!(graph.node_count(), 3);
         assert_eq!(graph.edge_count(), 2);
     }
+
+    #[test]
+    fn test_type_params_are_not_linked() {
+        let code = "
+        pub struct Cache<K: Hash, V> {
+            pub keys: Vec<K>,
+            pub value: V,
+            pub stats: Stats,
+        }
+
+        pub struct K {}
+
+        pub struct Stats {}
+            ";
+        let parsed = parse_rust_code(code);
+        let items = filter_structs(parsed);
+        let elements = from_item_to_structs(items);
+
+        let mut graph = DiGraph::<String, Edge>::new();
+        let node_indices = create_nodes_for_graph(&elements, &mut graph);
+
+        add_edges_for_graph(&elements, &mut graph, &node_indices);
+
+        assert_eq!(graph[node_indices[0]], "Cache<K: Hash, V>");
+        assert_eq!(graph.edge_count(), 1);
+        assert!(graph.contains_edge(node_indices[0], node_indices[2]));
+    }
+
+    #[test]
+    fn test_generic_instantiation_edges() {
+        let code = "
+        pub struct Holder {
+            pub inner: Wrapper<Inner>,
+            pub maybe: Option<Inner>,
+        }
+
+        pub struct Wrapper<T> {
+            pub value: T,
+        }
+
+        pub struct Inner {}
+            ";
+        let parsed = parse_rust_code(code);
+        let elements = from_item_to_structs(filter_structs(parsed));
+
+        let mut graph = DiGraph::<String, Edge>::new();
+        let node_indices = create_nodes_for_graph(&elements, &mut graph);
+        add_edges_for_graph(&elements, &mut graph, &node_indices);
+
+        let edges: Vec<(NodeIndex, NodeIndex, EdgeKind)> = graph
+            .edge_indices()
+            .map(|e| {
+                let (source, target) = graph.edge_endpoints(e).unwrap();
+                (source, target, graph[e].kind)
+            })
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                (node_indices[0], node_indices[1], EdgeKind::Field),
+                (node_indices[0], node_indices[2], EdgeKind::BoundWith),
+                (node_indices[0], node_indices[2], EdgeKind::Field),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_synthesized_binding_nodes() {
+        let code = "
+        pub struct Holder {
+            pub first: Wrapper<Inner>,
+            pub second: Wrapper<Inner>,
+        }
+
+        pub struct Wrapper<T> {
+            pub value: T,
+        }
+
+        pub struct Inner {}
+            ";
+        let parsed = parse_rust_code(code);
+        let elements = from_item_to_structs(filter_structs(parsed));
+
+        let mut graph = DiGraph::<String, Edge>::new();
+        let node_indices = create_nodes_for_graph(&elements, &mut graph);
+        let options = GraphOptions {
+            synthesize_bindings: true,
+            ..GraphOptions::default()
+        };
+        add_edges_for_graph_with_options(&elements, &mut graph, &node_indices, &options);
+
+        assert_eq!(graph.node_count(), 4);
+        let bound = graph
+            .node_indices()
+            .find(|&i| graph[i] == "Wrapper<Inner>")
+            .unwrap();
+        assert_eq!(graph.edges_connecting(node_indices[0], bound).count(), 2);
+        let binding = graph.find_edge(bound, node_indices[1]).unwrap();
+        assert_eq!(graph[binding].kind, EdgeKind::Binding);
+        let bound_with = graph.find_edge(bound, node_indices[2]).unwrap();
+        assert_eq!(graph[bound_with].kind, EdgeKind::BoundWith);
+    }
+
+    #[test]
+    fn test_external_and_primitive_leaf_nodes() {
+        let code = "
+        use chrono::{DateTime, Utc};
+
+        pub struct Order {
+            pub id: uuid::Uuid,
+            pub placed: DateTime<Utc>,
+            pub updated: Option<DateTime<Utc>>,
+            pub note: Option<String>,
+            pub total: u64,
+            pub lines: Vec<Line>,
+        }
+
+        pub struct Line {}
+            ";
+        let model = collect_model(parse_rust_code(code));
+        let labels = |options: &GraphOptions| {
+            let mut graph = DiGraph::<String, Edge>::new();
+            let node_indices = create_nodes_for_graph(&model.elements, &mut graph);
+            add_edges_for_graph_with_options(&model.elements, &mut graph, &node_indices, options);
+            assert!(graph
+                .node_indices()
+                .skip(2)
+                .all(|i| graph.neighbors(i).count() == 0));
+            let labels: Vec<String> = graph.node_weights().skip(2).cloned().collect();
+            (labels, graph.edge_count())
+        };
+
+        assert_eq!(labels(&GraphOptions::default()), (vec![], 1));
+        let external = GraphOptions {
+            external_types: true,
+            ..GraphOptions::default()
+        };
+        assert_eq!(
+            labels(&external),
+            (
+                vec!["Uuid", "DateTime", "Utc", "String"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+                7
+            )
+        );
+
+        assert_eq!(
+            external_crates(&model),
+            vec![
+                ("DateTime".to_string(), "chrono".to_string()),
+                ("Utc".to_string(), "chrono".to_string()),
+                ("Uuid".to_string(), "uuid".to_string())
+            ]
+        );
+        let grouped = GraphOptions {
+            primitive_types: true,
+            external_crates: external_crates(&model),
+            ..external
+        };
+        assert_eq!(
+            labels(&grouped).0,
+            vec![
+                "uuid::Uuid",
+                "chrono::DateTime",
+                "chrono::Utc",
+                "String",
+                "u64"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tuple_edges() {
+        let code = "
+        pub struct Pair(A, Vec<B>);
+
+        pub enum Either {
+            Both(A, Vec<B>),
+            Nested { pair: (A, (Option<B>, u32)) },
+        }
+
+        pub struct A {}
+
+        pub struct B {}
+            ";
+        let parsed = parse_rust_code(code);
+        let elements = from_item_to_structs(filter_structs(parsed));
+
+        let mut graph = DiGraph::<String, Edge>::new();
+        let node_indices = create_nodes_for_graph(&elements, &mut graph);
+        add_edges_for_graph(&elements, &mut graph, &node_indices);
+
+        let fields: Vec<Option<String>> = graph
+            .edge_weights()
+            .map(|edge| edge.field.clone())
+            .collect();
+        assert_eq!(
+            fields,
+            vec![
+                Some(".0".to_string()),
+                Some(".1".to_string()),
+                Some("Both.0".to_string()),
+                Some("Both.1".to_string()),
+                Some("Nested.pair".to_string()),
+                Some("Nested.pair".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_trait_object_and_fn_pointer_edges() {
+        let code = "
+        pub struct Dispatcher {
+            pub cb: Box<dyn Fn(Event) -> Reply>,
+            pub handler: fn(&Request) -> Response,
+            pub sink: Arc<dyn Sink + Send>,
+        }
+
+        pub trait Sink {
+            fn send(&self, reply: Reply);
+        }
+
+        pub struct Event {}
+
+        pub struct Reply {}
+
+        pub struct Request {}
+
+        pub struct Response {}
+            ";
+        let parsed = parse_rust_code(code);
+        let elements = from_item_to_structs(filter_elements(parsed));
+        assert_eq!(elements.len(), 6);
+
+        let mut graph = DiGraph::<String, Edge>::new();
+        let node_indices = create_nodes_for_graph(&elements, &mut graph);
+        add_edges_for_graph(&elements, &mut graph, &node_indices);
+
+        let targets: Vec<(String, String)> = graph
+            .edge_indices()
+            .map(|e| {
+                let (_, target) = graph.edge_endpoints(e).unwrap();
+                (graph[e].field.clone().unwrap(), graph[target].clone())
+            })
+            .collect();
+        let expected = [
+            ("cb", "Event"),
+            ("cb", "Reply"),
+            ("handler", "Request"),
+            ("handler", "Response"),
+            ("sink", "Sink"),
+        ];
+        assert_eq!(
+            targets,
+            expected
+                .iter()
+                .map(|(field, target)| (field.to_string(), target.to_string()))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_associated_type_edges() {
+        let code = "
+        pub struct Index<B, T: Source> {
+            pub key: <B as Storage>::Key,
+            pub item: T::Item,
+            pub other: <B as Storage>::Missing,
+        }
+
+        pub trait Storage {
+            type Key;
+        }
+
+        pub trait Source {
+            type Item;
+        }
+
+        pub struct Key {}
+
+        pub struct Item {}
+            ";
+        let parsed = parse_rust_code(code);
+        let elements = from_item_to_structs(filter_elements(parsed));
+
+        let mut graph = DiGraph::<String, Edge>::new();
+        let node_indices = create_nodes_for_graph(&elements, &mut graph);
+        add_edges_for_graph(&elements, &mut graph, &node_indices);
+
+        assert_eq!(graph.edge_count(), 2);
+        let key = graph.find_edge(node_indices[0], node_indices[1]).unwrap();
+        assert_eq!(graph[key].kind, EdgeKind::AssociatedType);
+        assert_eq!(graph[key].field, Some("key".to_string()));
+        let item = graph.find_edge(node_indices[0], node_indices[2]).unwrap();
+        assert_eq!(graph[item].field, Some("item".to_string()));
+    }
+
+    #[test]
+    fn test_collect_model() {
+        let code = "
+        pub use inner::Hidden;
+
+        pub struct Root {}
+
+        pub mod api {
+            use super::Root;
+
+            pub struct Request {}
+
+            mod detail {
+                pub enum Kind {}
+            }
+        }
+
+        mod inner {
+            pub struct Hidden {}
+        }
+
+        mod external;
+            ";
+        let model = collect_model(parse_rust_code(code));
+
+        let modules: Vec<String> = model.modules.iter().map(|m| m.path.join("::")).collect();
+        assert_eq!(modules, vec!["", "api", "api::detail", "inner", "external"]);
+        assert_eq!(model.modules[0].uses[0].path, vec!["inner", "Hidden"]);
+        assert_eq!(model.modules[1].uses[0].vis, Visibility::Private);
+
+        let elements: Vec<(String, &str)> = model
+            .elements
+            .iter()
+            .map(|e| (e.module().join("::"), e.name()))
+            .collect();
+        assert_eq!(
+            elements,
+            vec![
+                ("".to_string(), "Root"),
+                ("api".to_string(), "Request"),
+                ("api::detail".to_string(), "Kind"),
+                ("inner".to_string(), "Hidden"),
+            ]
+        );
+    }
 }
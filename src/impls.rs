@@ -1,13 +1,20 @@
 use crate::structures::*;
 use std::convert::TryFrom;
+use std::fmt;
 #[cfg(test)]
 use syn::{parse_str, ItemEnum, ItemStruct};
 use Option;
 
 use proc_macro2::Ident as SynIdent;
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
+use syn::punctuated::Punctuated;
+use syn::visit::{self, Visit};
 use syn::{
-    AngleBracketedGenericArguments, Field as SynField, GenericArgument, Item, PathArguments,
-    PathSegment, Type as SynType, TypePath, Variant as SynVariant,
+    AngleBracketedGenericArguments, Attribute, Expr, ExprLit, Field as SynField, GenericArgument,
+    GenericParam as SynGenericParam, Generics as SynGenerics, Item, ItemUse, Lit, Meta,
+    PathArguments, PathSegment, ReturnType, Token, TraitBoundModifier, TraitItem, Type as SynType,
+    TypeParamBound, TypePath, UseTree, Variant as SynVariant, WherePredicate as SynWherePredicate,
 };
 
 #[derive(Debug, Clone)]
@@ -27,36 +34,464 @@ impl TryFrom<Item> for Element {
                 let enum_element = EnumElement::from(item_enum);
                 Ok(Element::Enum(enum_element))
             }
+            Item::Trait(item_trait) => {
+                let trait_element = TraitElement::from(item_trait);
+                Ok(Element::Trait(trait_element))
+            }
             _ => Err(ConversionError::UnsupportedType),
         }
     }
 }
 
+impl Element {
+    pub fn name(&self) -> &str {
+        match self {
+            Element::Struct(struct_element) => &struct_element.name,
+            Element::Enum(enum_element) => &enum_element.name,
+            Element::Trait(trait_element) => &trait_element.name,
+        }
+    }
+
+    pub fn module(&self) -> &[String] {
+        match self {
+            Element::Struct(struct_element) => &struct_element.module,
+            Element::Enum(enum_element) => &enum_element.module,
+            Element::Trait(trait_element) => &trait_element.module,
+        }
+    }
+
+    pub fn set_module(&mut self, module: Vec<String>) {
+        match self {
+            Element::Struct(struct_element) => struct_element.module = module,
+            Element::Enum(enum_element) => enum_element.module = module,
+            Element::Trait(trait_element) => trait_element.module = module,
+        }
+    }
+
+    pub fn vis(&self) -> &Visibility {
+        match self {
+            Element::Struct(struct_element) => &struct_element.vis,
+            Element::Enum(enum_element) => &enum_element.vis,
+            Element::Trait(trait_element) => &trait_element.vis,
+        }
+    }
+
+    pub fn attrs(&self) -> &Attributes {
+        match self {
+            Element::Struct(struct_element) => &struct_element.attrs,
+            Element::Enum(enum_element) => &enum_element.attrs,
+            Element::Trait(trait_element) => &trait_element.attrs,
+        }
+    }
+
+    pub fn generics(&self) -> &Generics {
+        match self {
+            Element::Struct(struct_element) => &struct_element.generics,
+            Element::Enum(enum_element) => &enum_element.generics,
+            Element::Trait(trait_element) => &trait_element.generics,
+        }
+    }
+
+    /// Field behind the label of a field edge: `field` or `.0` in structs, `Variant.field` or
+    /// `Variant.0` in enums
+    pub fn field(&self, label: &str) -> Option<&Field> {
+        match self {
+            Element::Struct(struct_element) => struct_element
+                .fields
+                .iter()
+                .enumerate()
+                .find(|(position, field)| field.label(*position) == label)
+                .map(|(_, field)| field),
+            Element::Enum(enum_element) => {
+                let (variant_name, field_label) = label.split_once('.')?;
+                let variant = enum_element
+                    .variants
+                    .iter()
+                    .find(|variant| variant.name == variant_name)?;
+                variant
+                    .fields
+                    .iter()
+                    .enumerate()
+                    .find(|(position, field)| match &field.name {
+                        Some(name) => name == field_label,
+                        None => position.to_string() == field_label,
+                    })
+                    .map(|(_, field)| field)
+            }
+            Element::Trait(_) => None,
+        }
+    }
+
+    /// Name with generic parameters and where-clause: `Cache<K: Hash, V> where V: Clone`
+    pub fn label(&self) -> String {
+        let generics = self.generics();
+        let mut label = format!("{}{}", self.name(), generics);
+        if !generics.where_clause.is_empty() {
+            let predicates: Vec<String> = generics
+                .where_clause
+                .iter()
+                .map(|predicate| predicate.to_string())
+                .collect();
+            label.push_str(" where ");
+            label.push_str(&predicates.join(", "));
+        }
+        label
+    }
+}
+
 impl From<syn::ItemStruct> for StructElement {
     fn from(item_struct: syn::ItemStruct) -> Self {
         let name = item_struct.ident.to_string();
+        let vis = Visibility::from(item_struct.vis);
+        let attrs = Attributes::from(item_struct.attrs);
+        let generics = Generics::from(item_struct.generics);
+        let type_params = generics.type_params();
+        let unit = matches!(item_struct.fields, syn::Fields::Unit);
         let mut fields: Vec<Field> = Vec::new();
         for item_struct_field in item_struct.fields {
-            let field = Field::try_from(item_struct_field);
-            match field {
-                Ok(field) => fields.push(field),
-                Err(_) => {}
+            if let Ok(mut field) = Field::try_from(item_struct_field) {
+                field.ty.mark_type_params(&type_params);
+                fields.push(field);
             }
         }
 
-        StructElement { name, fields }
+        StructElement {
+            name,
+            module: Vec::new(),
+            vis,
+            attrs,
+            generics,
+            fields,
+            unit,
+        }
     }
 }
 
 impl From<syn::ItemEnum> for EnumElement {
     fn from(item_enum: syn::ItemEnum) -> Self {
         let name = item_enum.ident.to_string();
-        let variants: Vec<Variant> = item_enum.variants.into_iter().map(Variant::from).collect();
+        let vis = Visibility::from(item_enum.vis);
+        let attrs = Attributes::from(item_enum.attrs);
+        let generics = Generics::from(item_enum.generics);
+        let type_params = generics.type_params();
+        let mut variants: Vec<Variant> =
+            item_enum.variants.into_iter().map(Variant::from).collect();
+        for variant in &mut variants {
+            for field in &mut variant.fields {
+                field.ty.mark_type_params(&type_params);
+            }
+        }
 
-        EnumElement { name, variants }
+        EnumElement {
+            name,
+            module: Vec::new(),
+            vis,
+            attrs,
+            generics,
+            variants,
+        }
     }
 }
 
+impl From<syn::ItemTrait> for TraitElement {
+    fn from(item_trait: syn::ItemTrait) -> Self {
+        let name = item_trait.ident.to_string();
+        let vis = Visibility::from(item_trait.vis);
+        let attrs = Attributes::from(item_trait.attrs);
+        let generics = Generics::from(item_trait.generics);
+        let associated_types = item_trait
+            .items
+            .iter()
+            .filter_map(|item| match item {
+                TraitItem::Type(trait_type) => Some(trait_type.ident.to_string()),
+                _ => None,
+            })
+            .collect();
+
+        TraitElement {
+            name,
+            module: Vec::new(),
+            vis,
+            attrs,
+            generics,
+            associated_types,
+        }
+    }
+}
+
+impl From<syn::Visibility> for Visibility {
+    fn from(syn_vis: syn::Visibility) -> Self {
+        match syn_vis {
+            syn::Visibility::Public(_) => Visibility::Public,
+            syn::Visibility::Restricted(restricted) => {
+                let path = tokens_to_string(&restricted.path);
+                match restricted.in_token {
+                    Some(_) => Visibility::Restricted(format!("in {}", path)),
+                    None if path == "crate" => Visibility::Crate,
+                    None => Visibility::Restricted(path),
+                }
+            }
+            syn::Visibility::Inherited => Visibility::Private,
+        }
+    }
+}
+
+impl Visibility {
+    /// UML marker: `+` public, `~` package (`pub(crate)` and other restricted), `-` private
+    pub fn uml_marker(&self) -> char {
+        match self {
+            Visibility::Public => '+',
+            Visibility::Crate | Visibility::Restricted(_) => '~',
+            Visibility::Private => '-',
+        }
+    }
+}
+
+impl From<Vec<Attribute>> for Attributes {
+    fn from(syn_attrs: Vec<Attribute>) -> Self {
+        let mut attrs = Attributes::default();
+        let mut doc_lines: Vec<String> = Vec::new();
+        for attr in syn_attrs {
+            match &attr.meta {
+                Meta::NameValue(name_value) if name_value.path.is_ident("doc") => {
+                    if let Expr::Lit(ExprLit {
+                        lit: Lit::Str(doc), ..
+                    }) = &name_value.value
+                    {
+                        let line = doc.value();
+                        doc_lines.push(line.strip_prefix(' ').unwrap_or(&line).to_string());
+                    }
+                }
+                Meta::List(list) if list.path.is_ident("derive") => {
+                    if let Ok(paths) =
+                        list.parse_args_with(Punctuated::<syn::Path, Token![,]>::parse_terminated)
+                    {
+                        attrs.derives.extend(paths.iter().map(tokens_to_string));
+                    }
+                }
+                meta => attrs.other.push(tokens_to_string(meta)),
+            }
+        }
+        if !doc_lines.is_empty() {
+            attrs.docs = Some(doc_lines.join("\n").trim().to_string());
+        }
+        attrs
+    }
+}
+
+impl From<SynGenerics> for Generics {
+    fn from(syn_generics: SynGenerics) -> Self {
+        let params = syn_generics
+            .params
+            .into_iter()
+            .map(GenericParam::from)
+            .collect();
+        let where_clause = match syn_generics.where_clause {
+            Some(where_clause) => where_clause
+                .predicates
+                .into_iter()
+                .filter_map(|predicate| WherePredicate::try_from(predicate).ok())
+                .collect(),
+            None => Vec::new(),
+        };
+
+        Generics {
+            params,
+            where_clause,
+        }
+    }
+}
+
+impl Generics {
+    /// Names of the type parameters, without lifetimes and const generics
+    pub fn type_params(&self) -> Vec<String> {
+        self.params
+            .iter()
+            .filter_map(|param| match param {
+                GenericParam::Type { name, .. } => Some(name.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+impl From<SynGenericParam> for GenericParam {
+    fn from(syn_param: SynGenericParam) -> Self {
+        match syn_param {
+            SynGenericParam::Lifetime(lifetime_param) => GenericParam::Lifetime {
+                name: lifetime_param.lifetime.to_string(),
+                bounds: lifetime_param
+                    .bounds
+                    .iter()
+                    .map(|bound| bound.to_string())
+                    .collect(),
+            },
+            SynGenericParam::Type(type_param) => GenericParam::Type {
+                name: type_param.ident.to_string(),
+                bounds: type_param.bounds.iter().map(tokens_to_string).collect(),
+                default: type_param.default.as_ref().map(tokens_to_string),
+            },
+            SynGenericParam::Const(const_param) => GenericParam::Const {
+                name: const_param.ident.to_string(),
+                ty: tokens_to_string(&const_param.ty),
+            },
+        }
+    }
+}
+
+impl TryFrom<SynWherePredicate> for WherePredicate {
+    type Error = ConversionError;
+    fn try_from(syn_predicate: SynWherePredicate) -> Result<Self, Self::Error> {
+        match syn_predicate {
+            SynWherePredicate::Type(predicate) => Ok(WherePredicate {
+                bounded: tokens_to_string(&predicate.bounded_ty),
+                bounds: predicate.bounds.iter().map(tokens_to_string).collect(),
+            }),
+            SynWherePredicate::Lifetime(predicate) => Ok(WherePredicate {
+                bounded: predicate.lifetime.to_string(),
+                bounds: predicate
+                    .bounds
+                    .iter()
+                    .map(|bound| bound.to_string())
+                    .collect(),
+            }),
+            _ => Err(ConversionError::UnsupportedType),
+        }
+    }
+}
+
+impl fmt::Display for Generics {
+    /// Only the parameter list (`<K: Hash, V>`), the where-clause is shown by `Element::label`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.params.is_empty() {
+            return Ok(());
+        }
+        let params: Vec<String> = self.params.iter().map(|param| param.to_string()).collect();
+        write!(f, "<{}>", params.join(", "))
+    }
+}
+
+impl fmt::Display for GenericParam {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenericParam::Lifetime { name, bounds } | GenericParam::Type { name, bounds, .. } => {
+                write!(f, "{}", name)?;
+                if !bounds.is_empty() {
+                    write!(f, ": {}", bounds.join(" + "))?;
+                }
+                if let GenericParam::Type {
+                    default: Some(default),
+                    ..
+                } = self
+                {
+                    write!(f, " = {}", default)?;
+                }
+                Ok(())
+            }
+            GenericParam::Const { name, ty } => write!(f, "const {}: {}", name, ty),
+        }
+    }
+}
+
+impl fmt::Display for WherePredicate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.bounded, self.bounds.join(" + "))
+    }
+}
+
+/// Renders tokens back to source-like text: `Iterator < Item = u8 >` becomes `Iterator<Item = u8>`.
+/// Literals are kept as written, `rename = "a < b"` stays as is
+pub fn tokens_to_string<T: ToTokens>(value: &T) -> String {
+    let tokens = value.to_token_stream();
+    let mut literals = Vec::new();
+    collect_literals(tokens.clone(), &mut literals);
+    let text = tokens.to_string();
+    let mut rest = text.as_str();
+    let mut output = String::new();
+    for literal in literals {
+        if let Some(start) = rest.find(&literal) {
+            output.push_str(&normalize_spacing(&rest[..start]));
+            output.push_str(&literal);
+            rest = &rest[start + literal.len()..];
+        }
+    }
+    output.push_str(&normalize_spacing(rest));
+    output
+}
+
+fn collect_literals(tokens: TokenStream, literals: &mut Vec<String>) {
+    for token in tokens {
+        match token {
+            TokenTree::Literal(literal) => literals.push(literal.to_string()),
+            TokenTree::Group(group) => collect_literals(group.stream(), literals),
+            TokenTree::Ident(_) | TokenTree::Punct(_) => {}
+        }
+    }
+}
+
+fn normalize_spacing(text: &str) -> String {
+    let mut text = text.to_string();
+    for (from, to) in [
+        (" :: ", "::"),
+        (":: ", "::"),
+        (" <", "<"),
+        ("< ", "<"),
+        (" >", ">"),
+        (" ,", ","),
+        ("& ", "&"),
+        ("? ", "?"),
+        (" (", "("),
+        ("( ", "("),
+        (" )", ")"),
+        ("[ ", "["),
+        (" ]", "]"),
+        (" ;", ";"),
+    ] {
+        text = text.replace(from, to);
+    }
+    text
+}
+
+/// One `Use` per imported path of the tree: `pub use a::{B, c::*}` gives `a::B` and `a::c::*`
+pub fn uses_from_item(item_use: &ItemUse) -> Vec<Use> {
+    let mut paths = Vec::new();
+    flatten_use_tree(&item_use.tree, Vec::new(), &mut paths);
+    paths
+        .into_iter()
+        .map(|path| Use {
+            vis: Visibility::from(item_use.vis.clone()),
+            path,
+        })
+        .collect()
+}
+
+fn flatten_use_tree(tree: &UseTree, mut prefix: Vec<String>, paths: &mut Vec<Vec<String>>) {
+    match tree {
+        UseTree::Path(use_path) => {
+            prefix.push(use_path.ident.to_string());
+            flatten_use_tree(&use_path.tree, prefix, paths);
+        }
+        UseTree::Name(use_name) => {
+            prefix.push(use_name.ident.to_string());
+            paths.push(prefix);
+        }
+        UseTree::Rename(use_rename) => {
+            prefix.push(use_rename.ident.to_string());
+            paths.push(prefix);
+        }
+        UseTree::Glob(_) => {
+            prefix.push("*".to_string());
+            paths.push(prefix);
+        }
+        UseTree::Group(use_group) => {
+            for tree in &use_group.items {
+                flatten_use_tree(tree, prefix.clone(), paths);
+            }
+        }
+    }
+}
+
+#[allow(clippy::manual_map)]
 pub fn try_convert_option_ident_to_option_string(value: Option<SynIdent>) -> Option<String> {
     match value {
         Some(ident) => Some(ident.to_string()),
@@ -72,21 +507,52 @@ impl TryFrom<SynField> for Field {
     type Error = ConversionError;
     fn try_from(syn_field: SynField) -> Result<Self, Self::Error> {
         let name = try_convert_option_ident_to_option_string(syn_field.ident);
+        let vis = Visibility::from(syn_field.vis);
+        let attrs = Attributes::from(syn_field.attrs);
+        let mut qualified = QualifiedPaths::default();
+        qualified.visit_type(&syn_field.ty);
+        let ty = Type::from(syn_field.ty);
 
-        match syn_field.ty {
-            syn::Type::Path(type_path) => {
-                let ty = Type::from(type_path);
+        Ok(Field {
+            name,
+            vis,
+            attrs,
+            ty,
+            paths: qualified.0,
+        })
+    }
+}
 
-                return Ok(Field { name, ty });
-            }
-            _ => return Err(ConversionError::UnsupportedType),
+/// Paths with more than one segment in a type, `qself` types (`<T as Trait>::Item`) left out
+#[derive(Default)]
+struct QualifiedPaths(Vec<Vec<String>>);
+
+impl<'ast> Visit<'ast> for QualifiedPaths {
+    fn visit_type_path(&mut self, type_path: &'ast TypePath) {
+        if type_path.qself.is_none() && type_path.path.segments.len() > 1 {
+            let path = type_path.path.segments.iter();
+            self.0
+                .push(path.map(|segment| segment.ident.to_string()).collect());
+        }
+        visit::visit_type_path(self, type_path);
+    }
+}
+
+impl Field {
+    /// Field name, or its position (`.0`, `.1`) for tuple structs and tuple variants
+    pub fn label(&self, position: usize) -> String {
+        match &self.name {
+            Some(name) => name.clone(),
+            None => format!(".{}", position),
         }
     }
 }
 
 impl From<SynVariant> for Variant {
+    #[allow(clippy::single_match)]
     fn from(syn_variant: SynVariant) -> Self {
         let name = syn_variant.ident.to_string();
+        let attrs = Attributes::from(syn_variant.attrs);
         let mut fields: Vec<Field> = Vec::new();
         for field in syn_variant.fields {
             let try_from_field = Field::try_from(field);
@@ -98,22 +564,204 @@ impl From<SynVariant> for Variant {
             }
         }
 
-        Variant { name, fields }
+        Variant {
+            name,
+            attrs,
+            fields,
+        }
+    }
+}
+
+impl Type {
+    /// Replaces `Simple` names that refer to the given generic parameters with `TypeParam`,
+    /// so that `K` in `struct Cache<K>` is never linked to a local `struct K`
+    pub fn mark_type_params(&mut self, type_params: &[String]) {
+        match self {
+            Type::Simple(name) => {
+                if type_params.contains(name) {
+                    *self = Type::TypeParam(name.clone());
+                }
+            }
+            Type::TypeParam(_) | Type::Literal(_) => {}
+            Type::Vec(inner) | Type::Other(inner) | Type::Pointer { inner, .. } => {
+                inner.mark_type_params(type_params)
+            }
+            Type::Associated { self_ty, .. } => self_ty.mark_type_params(type_params),
+            Type::Generic(_, types)
+            | Type::Tuple(types)
+            | Type::TraitObject(types)
+            | Type::ImplTrait(types) => {
+                for ty in types {
+                    ty.mark_type_params(type_params);
+                }
+            }
+            Type::FnPointer { inputs, output, .. } => {
+                for ty in inputs {
+                    ty.mark_type_params(type_params);
+                }
+                if let Some(output) = output {
+                    output.mark_type_params(type_params);
+                }
+            }
+        }
+    }
+}
+
+impl Type {
+    /// Wrappers around every occurrence of the type `name`, outermost first: `["Vec", "Option"]`
+    /// for `T` in `Vec<Option<T>>`. References are `&`, raw pointers `*const` or `*mut`, tuples
+    /// `()`, trait objects `dyn`, `impl Trait` types `impl` and function pointers `fn` (or the
+    /// closure trait, `Fn`)
+    pub fn wrappers_of(&self, name: &str) -> Vec<Vec<String>> {
+        let mut found = Vec::new();
+        self.collect_wrappers(name, &mut Vec::new(), &mut found);
+        found
+    }
+
+    fn collect_wrappers(&self, name: &str, path: &mut Vec<String>, found: &mut Vec<Vec<String>>) {
+        let (wrapper, types): (&str, Vec<&Type>) = match self {
+            Type::Simple(simple) => {
+                if simple == name {
+                    found.push(path.clone());
+                }
+                return;
+            }
+            Type::TypeParam(_) | Type::Associated { .. } | Type::Literal(_) => return,
+            Type::Vec(inner) => ("Vec", vec![inner]),
+            Type::Other(inner) => ("&", vec![inner]),
+            Type::Pointer { mutable, inner } => (pointer_sigil(*mutable), vec![inner]),
+            Type::Generic(generic, args) => {
+                if generic == name {
+                    found.push(path.clone());
+                }
+                (generic, args.iter().collect())
+            }
+            Type::Tuple(types) => ("()", types.iter().collect()),
+            Type::TraitObject(bounds) => ("dyn", bounds.iter().collect()),
+            Type::ImplTrait(bounds) => ("impl", bounds.iter().collect()),
+            Type::FnPointer {
+                name: fn_name,
+                inputs,
+                output,
+            } => (
+                fn_name.as_deref().unwrap_or("fn"),
+                inputs.iter().chain(output.as_deref()).collect(),
+            ),
+        };
+        path.push(wrapper.to_string());
+        for ty in types {
+            ty.collect_wrappers(name, path, found);
+        }
+        path.pop();
+    }
+}
+
+/// `Vec<Option<T>>` for the wrappers `["Vec", "Option"]` around `T`, as returned by
+/// `Type::wrappers_of`. Other type arguments of the wrappers are left out
+pub fn wrapper_path(wrappers: &[String], name: &str) -> String {
+    wrappers
+        .iter()
+        .rev()
+        .fold(name.to_string(), |inner, wrapper| match wrapper.as_str() {
+            "&" => format!("&{}", inner),
+            "*const" | "*mut" => format!("{} {}", wrapper, inner),
+            "()" => format!("({}, ..)", inner),
+            "dyn" | "impl" => format!("{} {}", wrapper, inner),
+            _ => format!("{}<{}>", wrapper, inner),
+        })
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Type::Simple(name) | Type::TypeParam(name) => write!(f, "{}", name),
+            Type::Vec(inner) => write!(f, "Vec<{}>", inner),
+            Type::Generic(name, args) => write!(f, "{}<{}>", name, join_types(args)),
+            Type::Tuple(types) => write!(f, "({})", join_types(types)),
+            Type::Other(inner) => write!(f, "&{}", inner),
+            Type::Pointer { mutable, inner } => write!(f, "{} {}", pointer_sigil(*mutable), inner),
+            Type::TraitObject(bounds) => write!(f, "dyn {}", join_bounds(bounds)),
+            Type::ImplTrait(bounds) => write!(f, "impl {}", join_bounds(bounds)),
+            Type::FnPointer {
+                name,
+                inputs,
+                output,
+            } => {
+                write!(
+                    f,
+                    "{}({})",
+                    name.as_deref().unwrap_or("fn"),
+                    join_types(inputs)
+                )?;
+                match output {
+                    Some(output) => write!(f, " -> {}", output),
+                    None => Ok(()),
+                }
+            }
+            Type::Associated {
+                self_ty,
+                trait_name: Some(trait_name),
+                name,
+            } => write!(f, "<{} as {}>::{}", self_ty, trait_name, name),
+            Type::Associated {
+                self_ty,
+                trait_name: None,
+                name,
+            } => write!(f, "{}::{}", self_ty, name),
+            Type::Literal(value) => write!(f, "\"{}\"", value),
+        }
+    }
+}
+
+fn pointer_sigil(mutable: bool) -> &'static str {
+    match mutable {
+        true => "*mut",
+        false => "*const",
+    }
+}
+
+fn join_types(types: &[Type]) -> String {
+    let types: Vec<String> = types.iter().map(|ty| ty.to_string()).collect();
+    types.join(", ")
+}
+
+fn join_bounds(bounds: &[Type]) -> String {
+    let bounds: Vec<String> = bounds.iter().map(|ty| ty.to_string()).collect();
+    bounds.join(" + ")
+}
+
+/// Trait bounds of `dyn`/`impl` types, lifetimes and `?Sized` are skipped
+fn bounds_to_types<'a>(bounds: impl IntoIterator<Item = &'a TypeParamBound>) -> Vec<Type> {
+    bounds
+        .into_iter()
+        .filter_map(|bound| match bound {
+            TypeParamBound::Trait(trait_bound)
+                if matches!(trait_bound.modifier, TraitBoundModifier::None) =>
+            {
+                trait_bound.path.segments.last().cloned().map(Type::from)
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+fn return_type_to_type(output: ReturnType) -> Option<Box<Type>> {
+    match output {
+        ReturnType::Default => None,
+        ReturnType::Type(_, ty) => Some(Box::new(Type::from(*ty))),
     }
 }
 
 impl From<SynType> for Type {
+    /// Types without a dedicated variant (`!`, macros, `_`) are kept as their source text
     fn from(syn_type: SynType) -> Self {
         match syn_type {
-            SynType::Path(type_path) => {
-                let path = type_path.path;
-                let ident = path.segments.last().unwrap().ident.to_string();
-                Type::Simple(ident)
-            }
+            SynType::Path(type_path) => Type::from(type_path),
             SynType::Array(array) => {
                 let inner_type = *array.elem;
                 Type::Vec(Box::new(Type::from(inner_type)))
             }
+            SynType::Slice(slice) => Type::Vec(Box::new(Type::from(*slice.elem))),
             SynType::Tuple(tuple) => {
                 let mut tuple_vec = Vec::new();
                 for el in tuple.elems {
@@ -121,44 +769,108 @@ impl From<SynType> for Type {
                 }
                 Type::Tuple(tuple_vec)
             }
-            _ => panic!("Unsupported type: {:?}", syn_type),
+            SynType::Reference(reference) => Type::Other(Box::new(Type::from(*reference.elem))),
+            SynType::Ptr(pointer) => Type::Pointer {
+                mutable: pointer.mutability.is_some(),
+                inner: Box::new(Type::from(*pointer.elem)),
+            },
+            SynType::Paren(paren) => Type::from(*paren.elem),
+            SynType::Group(group) => Type::from(*group.elem),
+            SynType::TraitObject(trait_object) => {
+                Type::TraitObject(bounds_to_types(&trait_object.bounds))
+            }
+            SynType::ImplTrait(impl_trait) => Type::ImplTrait(bounds_to_types(&impl_trait.bounds)),
+            SynType::BareFn(bare_fn) => Type::FnPointer {
+                name: None,
+                inputs: bare_fn
+                    .inputs
+                    .into_iter()
+                    .map(|arg| Type::from(arg.ty))
+                    .collect(),
+                output: return_type_to_type(bare_fn.output),
+            },
+            _ => Type::Simple(tokens_to_string(&syn_type)),
         }
     }
 }
 
 impl From<TypePath> for Type {
+    /// Module prefixes are dropped (`std::string::String` is `String`), associated types are kept:
+    /// `<Backend as Storage>::Key` through `qself`, and `T::Item`/`Self::Item` when the segment
+    /// before the last one is a type (capitalized, as modules are snake_case)
     fn from(type_path: TypePath) -> Self {
-        let path = type_path.path;
-        let path_sement = path.segments.last();
-        match path_sement {
-            Some(seg) => return Type::from(seg.clone()),
+        let mut segments: Vec<PathSegment> = type_path.path.segments.into_iter().collect();
+        let last_segment = match segments.pop() {
+            Some(seg) => seg,
             None => {
                 panic!("Error in from TypePath to Type")
             }
+        };
+
+        if let Some(qself) = type_path.qself {
+            let trait_name = match qself.position {
+                0 => None,
+                position => segments
+                    .get(position - 1)
+                    .map(|segment| segment.ident.to_string()),
+            };
+            return Type::Associated {
+                self_ty: Box::new(Type::from(*qself.ty)),
+                trait_name,
+                name: last_segment.ident.to_string(),
+            };
+        }
+
+        match segments.pop() {
+            Some(self_segment)
+                if self_segment
+                    .ident
+                    .to_string()
+                    .starts_with(|c: char| c.is_ascii_uppercase()) =>
+            {
+                Type::Associated {
+                    self_ty: Box::new(Type::from(self_segment)),
+                    trait_name: None,
+                    name: last_segment.ident.to_string(),
+                }
+            }
+            _ => Type::from(last_segment),
         }
-        //Type::Simple(ident)
     }
 }
 
 impl From<PathSegment> for Type {
+    /// Lifetime and const arguments are dropped: `Wrapper<'a, T>` becomes `Generic("Wrapper", [T])`
     fn from(path_segment: PathSegment) -> Self {
-        let result = Type::try_from(path_segment.arguments);
-        match result {
-            Ok(res) => {
-                let ident_string = path_segment.ident.to_string();
-                if ident_string == "Vec".to_string() {
-                    return Type::Vec(Box::new(res));
+        let ident_string = path_segment.ident.to_string();
+        let args: Vec<Type> = match path_segment.arguments {
+            PathArguments::AngleBracketed(arguments) => arguments
+                .args
+                .into_iter()
+                .filter(|arg| matches!(arg, GenericArgument::Type(_)))
+                .map(Type::from)
+                .collect(),
+            PathArguments::Parenthesized(arguments) => {
+                return Type::FnPointer {
+                    name: Some(ident_string),
+                    inputs: arguments.inputs.into_iter().map(Type::from).collect(),
+                    output: return_type_to_type(arguments.output),
                 }
-
-                return res;
             }
-            Err(_) => return Type::Simple(path_segment.ident.to_string()),
+            PathArguments::None => Vec::new(),
+        };
+
+        match args.len() {
+            0 => Type::Simple(ident_string),
+            _ if ident_string == "Vec" => Type::Vec(Box::new(args.into_iter().next().unwrap())),
+            _ => Type::Generic(ident_string, args),
         }
     }
 }
 
 impl TryFrom<PathArguments> for Type {
     type Error = ConversionError;
+    #[allow(clippy::needless_return)]
     fn try_from(value: PathArguments) -> Result<Self, Self::Error> {
         match value {
             PathArguments::AngleBracketed(x) => {
@@ -261,16 +973,27 @@ mod tests {
 
         let expected_element = Element::Struct(StructElement {
             name: "C".to_string(),
+            module: Vec::new(),
+            vis: Visibility::Public,
+            attrs: Attributes::default(),
+            generics: Generics::default(),
             fields: vec![
                 Field {
                     name: Some("f".to_string()),
+                    vis: Visibility::Public,
+                    attrs: Attributes::default(),
                     ty: Type::Simple("F".to_string()),
+                    paths: Vec::new(),
                 },
                 Field {
                     name: Some("g".to_string()),
+                    vis: Visibility::Public,
+                    attrs: Attributes::default(),
                     ty: Type::Vec(Box::new(Type::Simple("G".to_string()))),
+                    paths: Vec::new(),
                 },
             ],
+            unit: false,
         });
 
         assert_eq!(element, expected_element);
@@ -294,6 +1017,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::collapsible_match)]
     fn test_element_from() {
         let code = "pub struct TestStruct { field1: u32 }";
         let item: Item = parse_str(code).unwrap();
@@ -351,4 +1075,267 @@ mod tests {
 
         assert_eq!(ty, Type::Simple("u32".to_string()));
     }
+
+    #[test]
+    fn test_generics_from() {
+        let code = "pub struct Cache<'a, K: Hash + Eq, V = String, const N: usize> where V: Clone { keys: [&'a K; N], values: Vec<V> }";
+        let item_struct: ItemStruct = parse_str(code).unwrap();
+        let struct_element = StructElement::from(item_struct);
+
+        assert_eq!(
+            struct_element.generics.params,
+            vec![
+                GenericParam::Lifetime {
+                    name: "'a".to_string(),
+                    bounds: vec![],
+                },
+                GenericParam::Type {
+                    name: "K".to_string(),
+                    bounds: vec!["Hash".to_string(), "Eq".to_string()],
+                    default: None,
+                },
+                GenericParam::Type {
+                    name: "V".to_string(),
+                    bounds: vec![],
+                    default: Some("String".to_string()),
+                },
+                GenericParam::Const {
+                    name: "N".to_string(),
+                    ty: "usize".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            struct_element.generics.where_clause,
+            vec![WherePredicate {
+                bounded: "V".to_string(),
+                bounds: vec!["Clone".to_string()],
+            }]
+        );
+
+        let element = Element::Struct(struct_element);
+        assert_eq!(
+            element.label(),
+            "Cache<'a, K: Hash + Eq, V = String, const N: usize> where V: Clone"
+        );
+    }
+
+    #[test]
+    fn test_type_params_marked() {
+        let code = "
+        pub enum Either<L, R: Iterator<Item = u8>> {
+            Left(L),
+            Right(Vec<R>),
+            Neither(Other),
+        }
+    ";
+        let item_enum: ItemEnum = parse_str(code).unwrap();
+        let enum_element = EnumElement::from(item_enum);
+
+        assert_eq!(
+            enum_element.generics.to_string(),
+            "<L, R: Iterator<Item = u8>>"
+        );
+        assert_eq!(
+            enum_element.variants[0].fields[0].ty,
+            Type::TypeParam("L".to_string())
+        );
+        assert_eq!(
+            enum_element.variants[1].fields[0].ty,
+            Type::Vec(Box::new(Type::TypeParam("R".to_string())))
+        );
+        assert_eq!(
+            enum_element.variants[2].fields[0].ty,
+            Type::Simple("Other".to_string())
+        );
+    }
+
+    #[test]
+    fn test_tuple_fields() {
+        let code = "pub struct Meters(f64, pub (A, &'static [B]));";
+        let item_struct: ItemStruct = parse_str(code).unwrap();
+        let struct_element = StructElement::from(item_struct);
+
+        assert_eq!(struct_element.fields.len(), 2);
+        assert_eq!(struct_element.fields[0].label(0), ".0");
+        assert_eq!(struct_element.fields[1].label(1), ".1");
+        assert_eq!(
+            struct_element.fields[1].ty,
+            Type::Tuple(vec![
+                Type::Simple("A".to_string()),
+                Type::Other(Box::new(Type::Vec(Box::new(Type::Simple("B".to_string()))))),
+            ])
+        );
+        assert_eq!(struct_element.fields[1].ty.to_string(), "(A, &Vec<B>)");
+
+        let code = "pub struct Raw(*const Node, *mut Node, &'static Node);";
+        let struct_element = StructElement::from(parse_str::<ItemStruct>(code).unwrap());
+        let types: Vec<String> = struct_element
+            .fields
+            .iter()
+            .map(|field| field.ty.to_string())
+            .collect();
+        assert_eq!(types, vec!["*const Node", "*mut Node", "&Node"]);
+        let wrappers = struct_element.fields[1].ty.wrappers_of("Node");
+        assert_eq!(wrapper_path(&wrappers[0], "Node"), "*mut Node");
+    }
+
+    #[test]
+    fn test_callable_and_trait_object_fields() {
+        let code = "
+        pub struct Dispatcher {
+            cb: Box<dyn Fn(Event) -> Reply + Send>,
+            handler: fn(&Request) -> Response,
+            sink: Arc<dyn Sink>,
+            iter: impl Iterator<Item = u8>,
+        }
+    ";
+        let item_struct: ItemStruct = parse_str(code).unwrap();
+        let struct_element = StructElement::from(item_struct);
+        let types: Vec<String> = struct_element
+            .fields
+            .iter()
+            .map(|field| field.ty.to_string())
+            .collect();
+
+        assert_eq!(
+            types,
+            vec![
+                "Box<dyn Fn(Event) -> Reply + Send>",
+                "fn(&Request) -> Response",
+                "Arc<dyn Sink>",
+                "impl Iterator",
+            ]
+        );
+        assert_eq!(
+            struct_element.fields[1].ty,
+            Type::FnPointer {
+                name: None,
+                inputs: vec![Type::Other(Box::new(Type::Simple("Request".to_string())))],
+                output: Some(Box::new(Type::Simple("Response".to_string()))),
+            }
+        );
+    }
+
+    #[test]
+    fn test_associated_types() {
+        let code = "
+        pub struct Index<T: Iterator, B> {
+            key: <B as Storage>::Key,
+            item: T::Item,
+            path: std::path::PathBuf,
+        }
+    ";
+        let item_struct: ItemStruct = parse_str(code).unwrap();
+        let struct_element = StructElement::from(item_struct);
+
+        assert_eq!(
+            struct_element.fields[0].ty,
+            Type::Associated {
+                self_ty: Box::new(Type::TypeParam("B".to_string())),
+                trait_name: Some("Storage".to_string()),
+                name: "Key".to_string(),
+            }
+        );
+        assert_eq!(
+            struct_element.fields[1].ty,
+            Type::Associated {
+                self_ty: Box::new(Type::TypeParam("T".to_string())),
+                trait_name: None,
+                name: "Item".to_string(),
+            }
+        );
+        assert_eq!(
+            struct_element.fields[2].ty,
+            Type::Simple("PathBuf".to_string())
+        );
+        assert_eq!(
+            struct_element.fields[0].ty.to_string(),
+            "<B as Storage>::Key"
+        );
+        assert_eq!(struct_element.fields[1].ty.to_string(), "T::Item");
+
+        let code = "pub trait Storage { type Key: Hash; const SIZE: usize; type Value; }";
+        let trait_element = TraitElement::from(parse_str::<syn::ItemTrait>(code).unwrap());
+        assert_eq!(trait_element.associated_types, vec!["Key", "Value"]);
+    }
+
+    #[test]
+    fn test_visibility_and_attributes() {
+        let code = r#"
+        /// Cached values.
+        ///
+        /// Evicted on every flush.
+        #[derive(Clone, serde::Serialize)]
+        #[serde(rename_all = "camelCase")]
+        pub(crate) struct Cache {
+            /// Number of hits
+            pub hits: u64,
+            pub(super) misses: u64,
+            pub(in crate::store) size: usize,
+            #[serde(skip)]
+            dirty: bool,
+            #[serde(rename = "amount (EUR)", alias = "a < b")]
+            amount: u64,
+        }
+    "#;
+        let item_struct: ItemStruct = parse_str(code).unwrap();
+        let struct_element = StructElement::from(item_struct);
+
+        assert_eq!(struct_element.vis, Visibility::Crate);
+        assert_eq!(
+            struct_element.attrs,
+            Attributes {
+                derives: vec!["Clone".to_string(), "serde::Serialize".to_string()],
+                other: vec![r#"serde(rename_all = "camelCase")"#.to_string()],
+                docs: Some("Cached values.\n\nEvicted on every flush.".to_string()),
+            }
+        );
+
+        let visibilities: Vec<&Visibility> = struct_element
+            .fields
+            .iter()
+            .map(|field| &field.vis)
+            .collect();
+        assert_eq!(
+            visibilities,
+            vec![
+                &Visibility::Public,
+                &Visibility::Restricted("super".to_string()),
+                &Visibility::Restricted("in crate::store".to_string()),
+                &Visibility::Private,
+                &Visibility::Private,
+            ]
+        );
+        let markers: String = visibilities.iter().map(|vis| vis.uml_marker()).collect();
+        assert_eq!(markers, "+~~--");
+        assert_eq!(
+            struct_element.fields[0].attrs.docs,
+            Some("Number of hits".to_string())
+        );
+        assert_eq!(struct_element.fields[3].attrs.other, vec!["serde(skip)"]);
+        assert_eq!(
+            struct_element.fields[4].attrs.other,
+            vec![r#"serde(rename = "amount (EUR)", alias = "a < b")"#]
+        );
+    }
+
+    #[test]
+    fn test_uses_from_item() {
+        let item_use: ItemUse =
+            parse_str("pub(crate) use crate::a::{B, c::*, D as E, self};").unwrap();
+        let uses = uses_from_item(&item_use);
+
+        let paths: Vec<String> = uses.iter().map(|u| u.path.join("::")).collect();
+        assert_eq!(
+            paths,
+            vec![
+                "crate::a::B",
+                "crate::a::c::*",
+                "crate::a::D",
+                "crate::a::self"
+            ]
+        );
+        assert!(uses.iter().all(|u| u.vis == Visibility::Crate));
+    }
 }
@@ -0,0 +1,354 @@
+use crate::serde_view::*;
+use crate::structures::*;
+use serde_json::{json, Map, Value};
+
+const DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// JSON Schema (draft 2020-12) of the local struct or enum `root`, as serialized by serde:
+/// renames, skips, `flatten` and enum tagging are honored. The root and every local type it
+/// reaches get a `$defs` entry, `None` when `root` is not a local struct or enum
+pub fn json_schema(model: &Model, root: &str) -> Option<Value> {
+    let model = wire_model(model, false);
+    find_data_type(&model, root)?;
+
+    let mut reachable = vec![root.to_string()];
+    let mut defs = Map::new();
+    while let Some(name) = reachable.pop() {
+        if defs.contains_key(&name) {
+            continue;
+        }
+        let Some(element) = find_data_type(&model, &name) else {
+            continue;
+        };
+        let mut referenced = Vec::new();
+        let mut schema = element_schema(&model, element, &mut referenced);
+        if let Some(docs) = &element.attrs().docs {
+            schema.insert("description".to_string(), json!(docs));
+        }
+        defs.insert(name, Value::Object(schema));
+        reachable.extend(referenced.into_iter().rev());
+    }
+
+    Some(json!({
+        "$schema": DRAFT,
+        "$ref": def_ref(root),
+        "$defs": defs,
+    }))
+}
+
+fn find_data_type<'a>(model: &'a Model, name: &str) -> Option<&'a Element> {
+    model.elements.iter().find(|element| {
+        element.name() == name && matches!(element, Element::Struct(_) | Element::Enum(_))
+    })
+}
+
+fn def_ref(name: &str) -> String {
+    format!("#/$defs/{}", name)
+}
+
+fn element_schema(
+    model: &Model,
+    element: &Element,
+    referenced: &mut Vec<String>,
+) -> Map<String, Value> {
+    match element {
+        // serde writes unit structs as `null`
+        Element::Struct(struct_element) if struct_element.unit => {
+            as_object(json!({ "type": "null" }))
+        }
+        Element::Struct(struct_element) => {
+            as_object(fields_schema(model, &struct_element.fields, referenced))
+        }
+        Element::Enum(enum_element) => {
+            let tagging = SerdeAttrs::from(&enum_element.attrs).tagging();
+            let variants: Vec<Value> = enum_element
+                .variants
+                .iter()
+                .map(|variant| variant_schema(model, variant, &tagging, referenced))
+                .collect();
+            as_object(json!({ "oneOf": variants }))
+        }
+        Element::Trait(_) => Map::new(),
+    }
+}
+
+fn as_object(value: Value) -> Map<String, Value> {
+    match value {
+        Value::Object(object) => object,
+        other => {
+            let mut object = Map::new();
+            object.insert("allOf".to_string(), json!([other]));
+            object
+        }
+    }
+}
+
+/// Named fields become an object, a single unnamed one the schema of its type and several
+/// unnamed ones a fixed-length array
+fn fields_schema(model: &Model, fields: &[Field], referenced: &mut Vec<String>) -> Value {
+    match fields {
+        [] => json!({ "type": "object" }),
+        [field] if field.name.is_none() => type_schema(model, &field.ty, referenced),
+        _ if fields[0].name.is_none() => {
+            let items: Vec<Value> = fields
+                .iter()
+                .map(|field| type_schema(model, &field.ty, referenced))
+                .collect();
+            json!({
+                "type": "array",
+                "prefixItems": items,
+                "minItems": fields.len(),
+                "maxItems": fields.len(),
+            })
+        }
+        _ => {
+            let mut properties = Map::new();
+            let mut required = Vec::new();
+            for (position, field) in fields.iter().enumerate() {
+                let mut schema = type_schema(model, &field.ty, referenced);
+                if let (Some(docs), Value::Object(schema)) = (&field.attrs.docs, &mut schema) {
+                    schema.insert("description".to_string(), json!(docs));
+                }
+                if !is_option(&field.ty) {
+                    required.push(field.label(position));
+                }
+                properties.insert(field.label(position), schema);
+            }
+            json!({
+                "type": "object",
+                "properties": properties,
+                "required": required,
+            })
+        }
+    }
+}
+
+fn variant_schema(
+    model: &Model,
+    variant: &Variant,
+    tagging: &Tagging,
+    referenced: &mut Vec<String>,
+) -> Value {
+    let unit = variant.fields.is_empty();
+    let payload = |referenced: &mut Vec<String>| fields_schema(model, &variant.fields, referenced);
+    let tag_only = |tag: &str| {
+        json!({
+            "type": "object",
+            "properties": { tag: { "const": variant.name } },
+            "required": [tag],
+        })
+    };
+    match tagging {
+        Tagging::External if unit => json!({ "const": variant.name }),
+        Tagging::External => json!({
+            "type": "object",
+            "properties": { variant.name.as_str(): payload(referenced) },
+            "required": [variant.name],
+            "additionalProperties": false,
+        }),
+        Tagging::Internal { tag } if unit => tag_only(tag),
+        Tagging::Internal { tag } => {
+            let mut payload = payload(referenced);
+            match payload.as_object_mut() {
+                Some(object) if variant.fields[0].name.is_some() => {
+                    object["properties"]
+                        .as_object_mut()
+                        .expect("object schema has properties")
+                        .shift_insert(0, tag.clone(), json!({ "const": variant.name }));
+                    object["required"]
+                        .as_array_mut()
+                        .expect("object schema has required")
+                        .insert(0, json!(tag));
+                    payload
+                }
+                _ => json!({ "allOf": [tag_only(tag), payload] }),
+            }
+        }
+        Tagging::Adjacent { tag, .. } if unit => tag_only(tag),
+        Tagging::Adjacent { tag, content } => json!({
+            "type": "object",
+            "properties": {
+                tag.as_str(): { "const": variant.name },
+                content.as_str(): payload(referenced),
+            },
+            "required": [tag, content],
+        }),
+        Tagging::Untagged if unit => json!({ "type": "null" }),
+        Tagging::Untagged => payload(referenced),
+    }
+}
+
+fn is_option(ty: &Type) -> bool {
+    matches!(ty, Type::Generic(name, _) if name == "Option")
+}
+
+fn type_schema(model: &Model, ty: &Type, referenced: &mut Vec<String>) -> Value {
+    match ty {
+        Type::Simple(name) => match name.as_str() {
+            "bool" => json!({ "type": "boolean" }),
+            "u8" | "u16" | "u32" | "u64" | "u128" | "usize" => {
+                json!({ "type": "integer", "minimum": 0 })
+            }
+            "i8" | "i16" | "i32" | "i64" | "i128" | "isize" => json!({ "type": "integer" }),
+            "f32" | "f64" => json!({ "type": "number" }),
+            "String" | "str" | "PathBuf" | "Path" => json!({ "type": "string" }),
+            "char" => json!({ "type": "string", "minLength": 1, "maxLength": 1 }),
+            _ if find_data_type(model, name).is_some() => {
+                referenced.push(name.clone());
+                json!({ "$ref": def_ref(name) })
+            }
+            _ => json!({}),
+        },
+        Type::Vec(inner) => {
+            json!({ "type": "array", "items": type_schema(model, inner, referenced) })
+        }
+        Type::Generic(name, args) => match (name.as_str(), args.as_slice()) {
+            ("Option", [inner]) => nullable(type_schema(model, inner, referenced)),
+            (
+                "Box" | "Rc" | "Arc" | "Cow" | "Cell" | "RefCell" | "Mutex" | "RwLock",
+                [.., inner],
+            ) => type_schema(model, inner, referenced),
+            ("VecDeque" | "LinkedList", [inner]) => {
+                json!({ "type": "array", "items": type_schema(model, inner, referenced) })
+            }
+            ("HashSet" | "BTreeSet", [inner, ..]) => json!({
+                "type": "array",
+                "items": type_schema(model, inner, referenced),
+                "uniqueItems": true,
+            }),
+            ("HashMap" | "BTreeMap", [_, value, ..]) => json!({
+                "type": "object",
+                "additionalProperties": type_schema(model, value, referenced),
+            }),
+            _ => type_schema(model, &Type::Simple(name.clone()), referenced),
+        },
+        Type::Tuple(types) if types.is_empty() => json!({ "type": "null" }),
+        Type::Tuple(types) => {
+            let items: Vec<Value> = types
+                .iter()
+                .map(|ty| type_schema(model, ty, referenced))
+                .collect();
+            json!({
+                "type": "array",
+                "prefixItems": items,
+                "minItems": types.len(),
+                "maxItems": types.len(),
+            })
+        }
+        Type::Other(inner) | Type::Pointer { inner, .. } => type_schema(model, inner, referenced),
+        Type::Literal(value) => json!({ "const": value }),
+        Type::TypeParam(_)
+        | Type::TraitObject(_)
+        | Type::ImplTrait(_)
+        | Type::FnPointer { .. }
+        | Type::Associated { .. } => json!({}),
+    }
+}
+
+/// `"type": ["integer", "null"]` for plain types, `anyOf` with null otherwise
+fn nullable(schema: Value) -> Value {
+    match schema.get("type") {
+        Some(Value::String(ty)) if schema.as_object().is_some_and(|s| s.len() == 1) => {
+            json!({ "type": [ty, "null"] })
+        }
+        _ => json!({ "anyOf": [schema, { "type": "null" }] }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::funcs::*;
+
+    const TEST_CODE: &str = r#"
+        /// A placed order
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        pub struct Order {
+            pub order_id: u64,
+            pub note: Option<String>,
+            pub lines: Vec<Line>,
+            pub status: Status,
+            #[serde(skip)]
+            pub cache: Cache,
+        }
+
+        pub struct Line(pub String, pub f64);
+
+        #[serde(tag = "kind")]
+        pub enum Status {
+            Open,
+            Shipped { tracking: Option<Tracking> },
+        }
+
+        pub struct Tracking {
+            pub code: String,
+        }
+
+        pub struct Cache {}
+
+        pub struct Marker;
+    "#;
+
+    #[test]
+    fn test_json_schema() {
+        let model = collect_model(parse_rust_code(TEST_CODE));
+        let schema = json_schema(&model, "Order").unwrap();
+
+        assert_eq!(schema["$schema"], DRAFT);
+        assert_eq!(schema["$ref"], "#/$defs/Order");
+        let defs: Vec<&String> = schema["$defs"].as_object().unwrap().keys().collect();
+        assert_eq!(defs, vec!["Order", "Line", "Status", "Tracking"]);
+
+        let order = &schema["$defs"]["Order"];
+        assert_eq!(order["description"], "A placed order");
+        assert_eq!(
+            order["properties"],
+            json!({
+                "orderId": { "type": "integer", "minimum": 0 },
+                "note": { "type": ["string", "null"] },
+                "lines": { "type": "array", "items": { "$ref": "#/$defs/Line" } },
+                "status": { "$ref": "#/$defs/Status" },
+            })
+        );
+        assert_eq!(order["required"], json!(["orderId", "lines", "status"]));
+        assert_eq!(
+            schema["$defs"]["Line"]["prefixItems"]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let unit = json_schema(&model, "Marker").unwrap();
+        assert_eq!(unit["$defs"]["Marker"], json!({ "type": "null" }));
+        let empty = json_schema(&model, "Cache").unwrap();
+        assert_eq!(empty["$defs"]["Cache"], json!({ "type": "object" }));
+    }
+
+    #[test]
+    fn test_json_schema_enum() {
+        let model = collect_model(parse_rust_code(TEST_CODE));
+        let schema = json_schema(&model, "Status").unwrap();
+
+        assert_eq!(
+            schema["$defs"]["Status"]["oneOf"],
+            json!([
+                {
+                    "type": "object",
+                    "properties": { "kind": { "const": "Open" } },
+                    "required": ["kind"],
+                },
+                {
+                    "type": "object",
+                    "properties": {
+                        "kind": { "const": "Shipped" },
+                        "tracking": { "anyOf": [{ "$ref": "#/$defs/Tracking" }, { "type": "null" }] },
+                    },
+                    "required": ["kind"],
+                },
+            ])
+        );
+        assert!(json_schema(&model, "Missing").is_none());
+    }
+}
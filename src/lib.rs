@@ -6,3 +6,54 @@ pub use funcs::*;
 
 pub mod impls;
 pub use impls::*;
+
+pub mod export;
+pub use export::*;
+
+pub mod public_api;
+pub use public_api::*;
+
+pub mod cfg;
+pub use cfg::*;
+
+pub mod diff;
+pub use diff::*;
+
+pub mod features;
+pub use features::*;
+
+pub mod serde_view;
+pub use serde_view::*;
+
+pub mod json_schema;
+pub use json_schema::*;
+
+pub mod er;
+pub use er::*;
+
+pub mod state_machine;
+pub use state_machine::*;
+
+pub mod module_graph;
+pub use module_graph::*;
+
+pub mod focus;
+pub use focus::*;
+
+pub mod references;
+pub use references::*;
+
+pub mod filter;
+pub use filter::*;
+
+pub mod cycles;
+pub use cycles::*;
+
+pub mod rules;
+pub use rules::*;
+
+pub mod semver;
+pub use semver::*;
+
+pub mod stats;
+pub use stats::*;
@@ -0,0 +1,213 @@
+use crate::funcs::*;
+use crate::public_api::resolve_use_path;
+use crate::structures::*;
+use petgraph::graph::DiGraph;
+use petgraph::visit::EdgeRef;
+
+/// Type graph aggregated by module
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ModuleGraph {
+    /// Module paths, the crate root is empty
+    pub modules: Vec<Vec<String>>,
+    pub edges: Vec<ModuleEdge>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ModuleEdge {
+    pub from: Vec<String>,
+    pub to: Vec<String>,
+    /// Type graph edges from a type in `from` to a type in `to`
+    pub references: usize,
+    /// `use` declarations in `from` that import from `to`
+    pub imports: usize,
+}
+
+impl ModuleEdge {
+    pub fn weight(&self) -> usize {
+        self.references + self.imports
+    }
+}
+
+/// One node per module and an edge when a type of one module refers to a type of another
+/// (through any edge of the type graph) or a module imports from another one.
+/// `use` paths that don't resolve to a local module (other crates) are ignored
+pub fn module_graph(model: &Model) -> ModuleGraph {
+    let modules: Vec<Vec<String>> = model.modules.iter().map(|m| m.path.clone()).collect();
+    let mut module_graph = ModuleGraph {
+        modules,
+        edges: Vec::new(),
+    };
+
+    let mut graph = DiGraph::<String, Edge>::new();
+    let node_indices = create_nodes_for_graph(&model.elements, &mut graph);
+    let options = GraphOptions {
+        modules: model.modules.clone(),
+        ..GraphOptions::default()
+    };
+    add_edges_for_graph_with_options(&model.elements, &mut graph, &node_indices, &options);
+    let module_of = |node| {
+        node_indices
+            .iter()
+            .position(|&i| i == node)
+            .map(|position| model.elements[position].module())
+    };
+    for edge in graph.edge_references() {
+        if let (Some(from), Some(to)) = (module_of(edge.source()), module_of(edge.target())) {
+            module_graph.edge_mut(from, to).references += 1;
+        }
+    }
+
+    for module in &model.modules {
+        for used in &module.uses {
+            let mut resolved = resolve_use_path(&module.path, &used.path);
+            if resolved.last().is_some_and(|segment| segment == "*") {
+                resolved.pop();
+            }
+            let target = (0..=resolved.len())
+                .rev()
+                .map(|len| &resolved[..len])
+                .find(|prefix| model.modules.iter().any(|m| m.path == *prefix));
+            if let Some(target) = target {
+                let target = target.to_vec();
+                module_graph.edge_mut(&module.path, &target).imports += 1;
+            }
+        }
+    }
+
+    module_graph
+        .edges
+        .retain(|edge| edge.from != edge.to && edge.weight() > 0);
+    module_graph
+}
+
+impl ModuleGraph {
+    fn edge_mut(&mut self, from: &[String], to: &[String]) -> &mut ModuleEdge {
+        let position = match self
+            .edges
+            .iter()
+            .position(|edge| edge.from == from && edge.to == to)
+        {
+            Some(position) => position,
+            None => {
+                self.edges.push(ModuleEdge {
+                    from: from.to_vec(),
+                    to: to.to_vec(),
+                    references: 0,
+                    imports: 0,
+                });
+                self.edges.len() - 1
+            }
+        };
+        &mut self.edges[position]
+    }
+}
+
+/// `crate` for the root, `a::b` otherwise
+pub fn module_name(path: &[String]) -> String {
+    if path.is_empty() {
+        "crate".to_string()
+    } else {
+        path.join("::")
+    }
+}
+
+/// Modules as boxes, edges labelled and thickened by their weight
+pub fn module_graph_to_dot(module_graph: &ModuleGraph) -> String {
+    let mut output = String::from("digraph {\n    node [ shape = box ]\n");
+    for (index, module) in module_graph.modules.iter().enumerate() {
+        output.push_str(&format!(
+            "    {} [ label = \"{}\" ]\n",
+            index,
+            module_name(module)
+        ));
+    }
+    let node = |path: &[String]| module_graph.modules.iter().position(|m| m == path);
+    for edge in &module_graph.edges {
+        if let (Some(from), Some(to)) = (node(&edge.from), node(&edge.to)) {
+            output.push_str(&format!(
+                "    {} -> {} [ label = \"{}\" tooltip = \"{} references, {} imports\" penwidth = {} ]\n",
+                from,
+                to,
+                edge.weight(),
+                edge.references,
+                edge.imports,
+                1 + edge.weight().ilog2()
+            ));
+        }
+    }
+    output.push_str("}\n");
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_CODE: &str = "
+        use domain::Order;
+
+        pub struct App {
+            pub orders: Vec<domain::Order>,
+            pub db: storage::Db,
+        }
+
+        pub mod domain {
+            use crate::storage::*;
+
+            pub struct Order {
+                pub customer: Customer,
+                pub row: Row,
+            }
+
+            pub struct Customer {
+                pub row: Row,
+            }
+        }
+
+        pub mod storage {
+            use std::collections::HashMap;
+
+            pub struct Db {}
+            pub struct Row {}
+        }
+    ";
+
+    #[test]
+    fn test_module_graph() {
+        let model = collect_model(parse_rust_code(TEST_CODE));
+        let module_graph = module_graph(&model);
+
+        assert_eq!(module_graph.modules.len(), 3);
+        let edges: Vec<(String, String, usize, usize)> = module_graph
+            .edges
+            .iter()
+            .map(|e| {
+                (
+                    module_name(&e.from),
+                    module_name(&e.to),
+                    e.references,
+                    e.imports,
+                )
+            })
+            .collect();
+        assert_eq!(
+            edges,
+            vec![
+                ("crate".to_string(), "domain".to_string(), 1, 1),
+                ("crate".to_string(), "storage".to_string(), 1, 0),
+                ("domain".to_string(), "storage".to_string(), 2, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_module_graph_to_dot() {
+        let model = collect_model(parse_rust_code(TEST_CODE));
+        let dot = module_graph_to_dot(&module_graph(&model));
+
+        assert!(dot.contains("    0 [ label = \"crate\" ]\n"));
+        assert!(dot.contains(
+            "    1 -> 2 [ label = \"3\" tooltip = \"2 references, 1 imports\" penwidth = 2 ]\n"
+        ));
+    }
+}
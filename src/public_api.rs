@@ -0,0 +1,155 @@
+use crate::structures::*;
+
+/// Part of the model that users of the crate can touch: `pub` elements in modules that are `pub`
+/// all the way from the root, or re-exported by a `pub use` in such a module.
+/// Struct fields that are not `pub` are removed, edges to the dropped types disappear with them
+pub fn public_api(model: &Model) -> Model {
+    let modules: Vec<Module> = model
+        .modules
+        .iter()
+        .filter(|module| is_module_public(model, &module.path))
+        .cloned()
+        .collect();
+    let elements = model
+        .elements
+        .iter()
+        .filter(|element| {
+            *element.vis() == Visibility::Public
+                && (is_module_public(model, element.module()) || is_reexported(&modules, element))
+        })
+        .cloned()
+        .map(|mut element| {
+            if let Element::Struct(struct_element) = &mut element {
+                struct_element
+                    .fields
+                    .retain(|field| field.vis == Visibility::Public);
+            }
+            element
+        })
+        .collect();
+
+    Model { modules, elements }
+}
+
+/// The root and every module on the path must be `pub`
+fn is_module_public(model: &Model, path: &[String]) -> bool {
+    (1..=path.len()).all(|len| {
+        model
+            .modules
+            .iter()
+            .find(|module| module.path == path[..len])
+            .is_some_and(|module| module.vis == Visibility::Public)
+    })
+}
+
+fn is_reexported(public_modules: &[Module], element: &Element) -> bool {
+    public_modules.iter().any(|module| {
+        module
+            .uses
+            .iter()
+            .filter(|item_use| item_use.vis == Visibility::Public)
+            .any(|item_use| {
+                let mut resolved = resolve_use_path(&module.path, &item_use.path);
+                match resolved.pop() {
+                    Some(last) if last == "*" => resolved == element.module(),
+                    Some(last) => last == element.name() && resolved == element.module(),
+                    None => false,
+                }
+            })
+    })
+}
+
+/// Absolute path of a `use` written in `module`: `crate::a::B`, `super::B` and `a::B`
+/// (relative to the module) are all resolved from the crate root
+pub fn resolve_use_path(module: &[String], path: &[String]) -> Vec<String> {
+    let mut resolved = module.to_vec();
+    for (position, segment) in path.iter().enumerate() {
+        match segment.as_str() {
+            "crate" if position == 0 => resolved.clear(),
+            "self" => {}
+            "super" => {
+                resolved.pop();
+            }
+            _ => resolved.push(segment.clone()),
+        }
+    }
+    resolved
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::funcs::*;
+
+    #[test]
+    fn test_public_api() {
+        let code = "
+        pub use inner::Reexported;
+        pub use crate::globbed::*;
+
+        pub struct Root {
+            pub request: api::Request,
+            secret: Secret,
+        }
+
+        struct Secret {}
+
+        pub mod api {
+            pub struct Request {
+                pub(crate) id: u64,
+            }
+
+            pub(crate) struct Internal {}
+
+            mod detail {
+                pub enum Kind {}
+            }
+        }
+
+        mod inner {
+            pub struct Reexported {}
+
+            pub struct NotReexported {}
+        }
+
+        mod globbed {
+            pub trait Everything {}
+        }
+            ";
+        let model = public_api(&collect_model(parse_rust_code(code)));
+
+        let names: Vec<&str> = model.elements.iter().map(|e| e.name()).collect();
+        assert_eq!(names, vec!["Root", "Request", "Reexported", "Everything"]);
+        match &model.elements[0] {
+            Element::Struct(root) => {
+                assert_eq!(root.fields.len(), 1);
+                assert_eq!(root.fields[0].name, Some("request".to_string()));
+            }
+            _ => panic!("Expected Element::Struct"),
+        }
+        match &model.elements[1] {
+            Element::Struct(request) => assert!(request.fields.is_empty()),
+            _ => panic!("Expected Element::Struct"),
+        }
+
+        let modules: Vec<String> = model.modules.iter().map(|m| m.path.join("::")).collect();
+        assert_eq!(modules, vec!["", "api"]);
+    }
+
+    #[test]
+    fn test_resolve_use_path() {
+        let module = vec!["a".to_string(), "b".to_string()];
+        let path = |text: &str| -> Vec<String> { text.split("::").map(String::from).collect() };
+
+        assert_eq!(
+            resolve_use_path(&module, &path("crate::x::Y")),
+            path("x::Y")
+        );
+        assert_eq!(resolve_use_path(&module, &path("super::Y")), path("a::Y"));
+        assert_eq!(
+            resolve_use_path(&module, &path("self::c::Y")),
+            path("a::b::c::Y")
+        );
+        assert_eq!(resolve_use_path(&module, &path("c::*")), path("a::b::c::*"));
+    }
+}
@@ -0,0 +1,195 @@
+use crate::impls::wrapper_path;
+use crate::structures::*;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use syn::spanned::Spanned;
+use syn::{File, Item};
+
+/// A struct field or enum variant field whose type mentions the queried type
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Reference {
+    pub file: PathBuf,
+    pub line: usize,
+    /// Struct or enum holding the field
+    pub owner: String,
+    /// `field` / `.0` in structs, `Variant.field` / `Variant.0` in enums, as on graph edges
+    pub member: String,
+    /// Type of the field as written
+    pub ty: Type,
+    /// The queried type
+    pub target: String,
+    /// Wrappers around the queried type, outermost first, see `Type::wrappers_of`
+    pub wrappers: Vec<String>,
+}
+
+/// Every field of every struct and enum variant in `files` that references `type_name`,
+/// once per occurrence (`(Order, Order)` gives two). Files come from `load_files`
+pub fn find_references(files: &[(PathBuf, File)], type_name: &str) -> Vec<Reference> {
+    let mut references = Vec::new();
+    for (path, file) in files {
+        collect_references(path, &file.items, type_name, &mut references);
+    }
+    references
+}
+
+fn collect_references(
+    path: &Path,
+    items: &[Item],
+    type_name: &str,
+    references: &mut Vec<Reference>,
+) {
+    for item in items {
+        match (item, Element::try_from(item.clone())) {
+            (Item::Struct(item_struct), Ok(Element::Struct(struct_element))) => {
+                let fields = struct_element.fields.iter().zip(&item_struct.fields);
+                for (position, (field, syn_field)) in fields.enumerate() {
+                    let member = field.label(position);
+                    let owner = &struct_element.name;
+                    references.extend(field_references(
+                        path, owner, member, field, syn_field, type_name,
+                    ));
+                }
+            }
+            (Item::Enum(item_enum), Ok(Element::Enum(enum_element))) => {
+                for (variant, syn_variant) in enum_element.variants.iter().zip(&item_enum.variants)
+                {
+                    let fields = variant.fields.iter().zip(&syn_variant.fields);
+                    for (position, (field, syn_field)) in fields.enumerate() {
+                        let member = match &field.name {
+                            Some(name) => format!("{}.{}", variant.name, name),
+                            None => format!("{}.{}", variant.name, position),
+                        };
+                        let owner = &enum_element.name;
+                        references.extend(field_references(
+                            path, owner, member, field, syn_field, type_name,
+                        ));
+                    }
+                }
+            }
+            (Item::Mod(item_mod), _) => {
+                if let Some((_, items)) = &item_mod.content {
+                    collect_references(path, items, type_name, references);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// One reference per occurrence of `type_name` in the field type, located at the field name
+/// (or at the type of an unnamed field)
+fn field_references(
+    path: &Path,
+    owner: &str,
+    member: String,
+    field: &Field,
+    syn_field: &syn::Field,
+    type_name: &str,
+) -> Vec<Reference> {
+    let span = match &syn_field.ident {
+        Some(ident) => ident.span(),
+        None => syn_field.ty.span(),
+    };
+    field
+        .ty
+        .wrappers_of(type_name)
+        .into_iter()
+        .map(|wrappers| Reference {
+            file: path.to_path_buf(),
+            line: span.start().line,
+            owner: owner.to_string(),
+            member: member.clone(),
+            ty: field.ty.clone(),
+            target: type_name.to_string(),
+            wrappers,
+        })
+        .collect()
+}
+
+impl Reference {
+    /// `Vec<Option<T>>`, the type with only the wrappers leading to the queried type
+    pub fn wrapper_path(&self) -> String {
+        wrapper_path(&self.wrappers, &self.target)
+    }
+}
+
+/// `src/shop.rs:12: Shop.orders: Vec<Order>`, followed by the wrapper path when the type has
+/// more in it: `Shop.index: HashMap<u64, Order> (HashMap<Order>)`
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}.{}: {}",
+            self.file.display(),
+            self.line,
+            self.owner,
+            self.member.trim_start_matches('.'),
+            self.ty
+        )?;
+        let wrapper_path = self.wrapper_path();
+        if wrapper_path != self.ty.to_string() {
+            write!(f, " ({})", wrapper_path)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::funcs::*;
+
+    const TEST_CODE: &str = "pub struct Shop {
+    pub orders: Vec<Option<Order>>,
+    pub index: HashMap<u64, Order>,
+}
+
+pub enum Event {
+    Placed(Order),
+    Merged { from: (Order, Order) },
+}
+
+mod archive {
+    pub struct Archived(pub Box<Order>);
+}
+
+pub struct Order {}
+";
+
+    #[test]
+    fn test_find_references() {
+        let files = vec![(PathBuf::from("src/shop.rs"), parse_rust_code(TEST_CODE))];
+        let references = find_references(&files, "Order");
+
+        let found: Vec<(usize, &str, &str, String)> = references
+            .iter()
+            .map(|r| {
+                (
+                    r.line,
+                    r.owner.as_str(),
+                    r.member.as_str(),
+                    r.wrapper_path(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                (2, "Shop", "orders", "Vec<Option<Order>>".to_string()),
+                (3, "Shop", "index", "HashMap<Order>".to_string()),
+                (7, "Event", "Placed.0", "Order".to_string()),
+                (8, "Event", "Merged.from", "(Order, ..)".to_string()),
+                (8, "Event", "Merged.from", "(Order, ..)".to_string()),
+                (12, "Archived", ".0", "Box<Order>".to_string()),
+            ]
+        );
+        assert_eq!(
+            references[1].to_string(),
+            "src/shop.rs:3: Shop.index: HashMap<u64, Order> (HashMap<Order>)"
+        );
+        assert_eq!(
+            references[5].to_string(),
+            "src/shop.rs:12: Archived.0: Box<Order>"
+        );
+    }
+}
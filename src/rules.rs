@@ -0,0 +1,349 @@
+use crate::filter::{full_path, glob_matches};
+use crate::references::find_references;
+use crate::structures::*;
+use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::visit::EdgeRef;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use syn::File;
+
+/// Architecture rules from a TOML file, one `[[rule]]` table each:
+/// ```toml
+/// [[rule]]
+/// name = "the domain doesn't know about storage"
+/// from = "domain"
+/// forbid = ["infra", "db::Row"]
+///
+/// [[rule]]
+/// from = "api"
+/// allow = ["domain", "shared::*"]
+/// ```
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Rules {
+    pub rules: Vec<Rule>,
+}
+
+/// Patterns are globs over the full path of a type (`crate::` may be left out), a module
+/// pattern also covers everything below it: `domain` matches `crate::domain::order::Order`
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Rule {
+    pub name: Option<String>,
+    /// Types the rule applies to
+    pub from: String,
+    /// Types they must not reference
+    pub forbid: Vec<String>,
+    /// When not empty, the only types they may reference besides those matched by `from`
+    pub allow: Vec<String>,
+}
+
+/// Edge between two types breaking a rule
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Violation {
+    /// Name of the rule, or a description of it
+    pub rule: String,
+    /// Full path of the referencing type
+    pub source: String,
+    /// Full path of the referenced type
+    pub target: String,
+    /// Edge label: `field`, `.0`, `Variant.field`, empty for edges without one
+    pub member: String,
+    /// File and line of the field, filled by `locate_violations`
+    pub location: Option<(PathBuf, usize)>,
+}
+
+impl Rules {
+    pub fn parse(rules: &str) -> Result<Self, String> {
+        let table: toml::Table = toml::from_str(rules).map_err(|err| err.to_string())?;
+        let Some(entries) = table.get("rule") else {
+            return Ok(Rules::default());
+        };
+        let entries = entries
+            .as_array()
+            .ok_or("`rule` must be an array of tables, [[rule]]")?;
+        let rules = entries
+            .iter()
+            .enumerate()
+            .map(|(position, entry)| {
+                let rule = Rule {
+                    name: entry
+                        .get("name")
+                        .and_then(|name| name.as_str())
+                        .map(String::from),
+                    from: entry
+                        .get("from")
+                        .and_then(|from| from.as_str())
+                        .ok_or(format!("rule {}: `from` must be a string", position + 1))?
+                        .to_string(),
+                    forbid: patterns(entry.get("forbid")),
+                    allow: patterns(entry.get("allow")),
+                };
+                match rule.forbid.is_empty() && rule.allow.is_empty() {
+                    true => Err(format!("rule {}: needs `forbid` or `allow`", position + 1)),
+                    false => Ok(rule),
+                }
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Rules { rules })
+    }
+
+    pub fn read(path: &Path) -> io::Result<Self> {
+        let rules = fs::read_to_string(path)?;
+        Rules::parse(&rules).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+/// A string or an array of strings
+fn patterns(value: Option<&toml::Value>) -> Vec<String> {
+    match value {
+        Some(toml::Value::String(pattern)) => vec![pattern.clone()],
+        Some(toml::Value::Array(patterns)) => patterns
+            .iter()
+            .filter_map(|pattern| pattern.as_str().map(String::from))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let short = path.strip_prefix("crate::").unwrap_or(path);
+    [pattern.to_string(), format!("{}::*", pattern)]
+        .iter()
+        .any(|glob| glob_matches(glob, path) || glob_matches(glob, short))
+}
+
+impl Rule {
+    pub fn allows(&self, source: &str, target: &str) -> bool {
+        if !pattern_matches(&self.from, source) {
+            return true;
+        }
+        let matches = |patterns: &[String]| patterns.iter().any(|p| pattern_matches(p, target));
+        let forbidden = matches(&self.forbid);
+        let not_allowed =
+            !self.allow.is_empty() && !matches(&self.allow) && !pattern_matches(&self.from, target);
+        !forbidden && !not_allowed
+    }
+
+    /// The name, or `domain must not reference infra, db::Row`
+    pub fn describe(&self) -> String {
+        if let Some(name) = &self.name {
+            return name.clone();
+        }
+        match self.allow.is_empty() {
+            true => format!(
+                "{} must not reference {}",
+                self.from,
+                self.forbid.join(", ")
+            ),
+            false if self.forbid.is_empty() => {
+                format!("{} may only reference {}", self.from, self.allow.join(", "))
+            }
+            false => format!(
+                "{} may only reference {}, not {}",
+                self.from,
+                self.allow.join(", "),
+                self.forbid.join(", ")
+            ),
+        }
+    }
+}
+
+/// Every edge between two types of the graph built by `create_nodes_for_graph` and
+/// `add_edges_for_graph` that breaks a rule, once per rule, in edge order
+pub fn check_rules(
+    elements: &[Element],
+    graph: &DiGraph<String, Edge>,
+    node_indices: &[NodeIndex],
+    rules: &Rules,
+) -> Vec<Violation> {
+    let path_of = |node: NodeIndex| {
+        node_indices
+            .iter()
+            .position(|&i| i == node)
+            .map(|position| full_path(elements[position].module(), elements[position].name()))
+    };
+    let mut violations = Vec::new();
+    for edge in graph.edge_references() {
+        let (Some(source), Some(target)) = (path_of(edge.source()), path_of(edge.target())) else {
+            continue;
+        };
+        for rule in &rules.rules {
+            if !rule.allows(&source, &target) {
+                violations.push(Violation {
+                    rule: rule.describe(),
+                    source: source.clone(),
+                    target: target.clone(),
+                    member: edge.weight().field.clone().unwrap_or_default(),
+                    location: None,
+                });
+            }
+        }
+    }
+    violations
+}
+
+/// Sets the location of every violation found in `files` (from `load_files`), by the field
+/// that references the target
+pub fn locate_violations(violations: &mut [Violation], files: &[(PathBuf, File)]) {
+    for violation in violations {
+        let name = |path: &str| path.rsplit("::").next().unwrap_or(path).to_string();
+        let (source, target) = (name(&violation.source), name(&violation.target));
+        violation.location = find_references(files, &target)
+            .into_iter()
+            .find(|reference| reference.owner == source && reference.member == violation.member)
+            .map(|reference| (reference.file, reference.line));
+    }
+}
+
+/// `src/api.rs:12: api::Handler.row -> db::Row: api may only reference domain`
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some((file, line)) = &self.location {
+            write!(f, "{}:{}: ", file.display(), line)?;
+        }
+        let short = |path: &str| path.strip_prefix("crate::").unwrap_or(path).to_string();
+        write!(f, "{}", short(&self.source))?;
+        if !self.member.is_empty() {
+            write!(f, ".{}", self.member.trim_start_matches('.'))?;
+        }
+        write!(f, " -> {}: {}", short(&self.target), self.rule)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::funcs::*;
+
+    const TEST_CODE: &str = "pub mod domain {
+    pub struct Order {
+        pub row: crate::infra::Row,
+        pub money: crate::shared::Money,
+    }
+}
+
+pub mod infra {
+    pub struct Row {}
+}
+
+pub mod shared {
+    pub struct Money {}
+}
+
+pub mod api {
+    pub struct Handler {
+        pub order: crate::domain::Order,
+        pub row: crate::infra::Row,
+    }
+}
+";
+
+    const RULES: &str = r#"
+        [[rule]]
+        name = "the domain doesn't know about storage"
+        from = "domain"
+        forbid = "infra"
+
+        [[rule]]
+        from = "api::*"
+        allow = ["domain", "shared"]
+    "#;
+
+    #[test]
+    fn test_parse_rules() {
+        let rules = Rules::parse(RULES).unwrap();
+        assert_eq!(rules.rules.len(), 2);
+        assert_eq!(rules.rules[0].forbid, vec!["infra"]);
+        assert_eq!(
+            rules.rules[1].describe(),
+            "api::* may only reference domain, shared"
+        );
+
+        assert!(Rules::parse("[[rule]]\nfrom = \"api\"").is_err());
+        assert!(Rules::parse("[[rule]]\nforbid = [\"api\"]").is_err());
+        assert_eq!(Rules::parse("").unwrap(), Rules::default());
+    }
+
+    #[test]
+    fn test_check_rules() {
+        let files = vec![(PathBuf::from("src/lib.rs"), parse_rust_code(TEST_CODE))];
+        let model = collect_model(parse_rust_code(TEST_CODE));
+        let mut graph = DiGraph::<String, Edge>::new();
+        let node_indices = create_nodes_for_graph(&model.elements, &mut graph);
+        add_edges_for_graph(&model.elements, &mut graph, &node_indices);
+
+        let mut violations = check_rules(
+            &model.elements,
+            &graph,
+            &node_indices,
+            &Rules::parse(RULES).unwrap(),
+        );
+        locate_violations(&mut violations, &files);
+        let found: Vec<String> = violations.iter().map(|v| v.to_string()).collect();
+        assert_eq!(
+            found,
+            vec![
+                "src/lib.rs:3: domain::Order.row -> infra::Row: the domain doesn't know about storage",
+                "src/lib.rs:19: api::Handler.row -> infra::Row: api::* may only reference domain, shared",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_rules_same_names() {
+        let code = "pub mod db {
+    pub struct Row {}
+}
+
+pub mod api {
+    use crate::db;
+
+    pub struct Row {}
+
+    pub struct Handler {
+        pub row: Row,
+        pub stored: db::Row,
+    }
+}
+
+pub mod jobs {
+    use crate::db::Row;
+
+    pub struct Job {
+        pub row: Row,
+    }
+}
+
+pub mod report {
+    pub struct Report {
+        pub row: Row,
+    }
+}
+";
+        let model = collect_model(parse_rust_code(code));
+        let options = GraphOptions {
+            modules: model.modules.clone(),
+            ..GraphOptions::default()
+        };
+        let mut graph = DiGraph::<String, Edge>::new();
+        let node_indices = create_nodes_for_graph(&model.elements, &mut graph);
+        add_edges_for_graph_with_options(&model.elements, &mut graph, &node_indices, &options);
+
+        let rules = Rules::parse("[[rule]]\nfrom = \"*\"\nforbid = \"db\"").unwrap();
+        let violations = check_rules(&model.elements, &graph, &node_indices, &rules);
+        let found: Vec<(&str, &str)> = violations
+            .iter()
+            .map(|v| (v.source.as_str(), v.member.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("crate::api::Handler", "stored"),
+                ("crate::jobs::Job", "row")
+            ]
+        );
+        // `Row` is ambiguous in `report`, which imports neither
+        assert_eq!(graph.edges(node_indices[4]).count(), 0);
+    }
+}
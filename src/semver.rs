@@ -0,0 +1,527 @@
+use crate::public_api::public_api;
+use crate::structures::*;
+use std::fmt;
+
+/// Version bump a change needs, in increasing order
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+pub enum Impact {
+    Patch,
+    Minor,
+    Major,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ApiChangeKind {
+    TypeAdded,
+    TypeRemoved,
+    /// An existing type became reachable from outside the crate
+    TypeMadePublic,
+    /// Still there, but no longer reachable from outside the crate
+    TypeMadePrivate,
+    /// Struct to enum, enum to trait, ...
+    KindChanged,
+    FieldAdded,
+    FieldRemoved,
+    FieldMadePublic,
+    FieldMadePrivate,
+    FieldTypeChanged,
+    VariantAdded,
+    VariantRemoved,
+    /// Fields of the variant added, removed or of another type
+    VariantChanged,
+    NonExhaustiveAdded,
+    NonExhaustiveRemoved,
+}
+
+/// Change to the public data types between two versions
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ApiChange {
+    /// `a::Order`, `a::Order.note`, `a::Status::Closed`
+    pub path: String,
+    pub kind: ApiChangeKind,
+    pub impact: Impact,
+    /// `u32 -> Money` for type changes, `struct -> enum` for kind changes
+    pub detail: Option<String>,
+}
+
+/// Changes to the public structs and enums from `old` to `new`, with their semver impact.
+/// Types are matched by module path and name, fields by name or position, variants by name.
+/// Fields added to a struct whose fields are all `pub` break struct literals and patterns, so
+/// they are major unless the struct is `#[non_exhaustive]`
+pub fn api_changes(old: &Model, new: &Model) -> Vec<ApiChange> {
+    let old_public = public_api(old);
+    let new_public = public_api(new);
+    let find = |model: &Model, element: &Element| {
+        model
+            .elements
+            .iter()
+            .find(|other| other.name() == element.name() && other.module() == element.module())
+            .cloned()
+    };
+
+    let mut changes = Vec::new();
+    for old_element in &old_public.elements {
+        let path = type_path(old_element);
+        let Some(new_element) = find(new, old_element) else {
+            changes.push(ApiChange::new(
+                path,
+                ApiChangeKind::TypeRemoved,
+                Impact::Major,
+            ));
+            continue;
+        };
+        if find(&new_public, old_element).is_none() {
+            changes.push(ApiChange::new(
+                path,
+                ApiChangeKind::TypeMadePrivate,
+                Impact::Major,
+            ));
+            continue;
+        }
+        // Private fields are gone from the public API, compare the full elements
+        let old_element = find(old, old_element).unwrap();
+        compare_elements(&old_element, &new_element, &mut changes);
+    }
+    for new_element in &new_public.elements {
+        if find(&old_public, new_element).is_none() {
+            let kind = match find(old, new_element) {
+                Some(_) => ApiChangeKind::TypeMadePublic,
+                None => ApiChangeKind::TypeAdded,
+            };
+            changes.push(ApiChange::new(type_path(new_element), kind, Impact::Minor));
+        }
+    }
+    changes
+}
+
+/// Highest impact of the changes, `Patch` when there are none
+pub fn required_bump(changes: &[ApiChange]) -> Impact {
+    changes
+        .iter()
+        .map(|change| change.impact)
+        .max()
+        .unwrap_or(Impact::Patch)
+}
+
+fn type_path(element: &Element) -> String {
+    let mut path = element.module().to_vec();
+    path.push(element.name().to_string());
+    path.join("::")
+}
+
+fn is_non_exhaustive(element: &Element) -> bool {
+    element
+        .attrs()
+        .other
+        .iter()
+        .any(|attr| attr == "non_exhaustive")
+}
+
+fn kind_name(element: &Element) -> &'static str {
+    match element {
+        Element::Struct(_) => "struct",
+        Element::Enum(_) => "enum",
+        Element::Trait(_) => "trait",
+    }
+}
+
+fn compare_elements(old: &Element, new: &Element, changes: &mut Vec<ApiChange>) {
+    let path = type_path(new);
+    match (is_non_exhaustive(old), is_non_exhaustive(new)) {
+        (false, true) => changes.push(ApiChange::new(
+            path.clone(),
+            ApiChangeKind::NonExhaustiveAdded,
+            Impact::Major,
+        )),
+        (true, false) => changes.push(ApiChange::new(
+            path.clone(),
+            ApiChangeKind::NonExhaustiveRemoved,
+            Impact::Minor,
+        )),
+        _ => {}
+    }
+    match (old, new) {
+        (Element::Struct(old_struct), Element::Struct(new_struct)) => compare_fields(
+            &path,
+            old_struct,
+            new_struct,
+            is_non_exhaustive(old),
+            changes,
+        ),
+        (Element::Enum(old_enum), Element::Enum(new_enum)) => {
+            compare_variants(&path, old_enum, new_enum, is_non_exhaustive(old), changes)
+        }
+        (Element::Trait(_), Element::Trait(_)) => {}
+        _ => {
+            changes.push(
+                ApiChange::new(path, ApiChangeKind::KindChanged, Impact::Major)
+                    .with_detail(format!("{} -> {}", kind_name(old), kind_name(new))),
+            )
+        }
+    }
+}
+
+fn compare_fields(
+    path: &str,
+    old: &StructElement,
+    new: &StructElement,
+    non_exhaustive: bool,
+    changes: &mut Vec<ApiChange>,
+) {
+    let labelled = |element: &StructElement| -> Vec<(String, Field)> {
+        element
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(position, field)| (field.label(position), field.clone()))
+            .collect()
+    };
+    let (old_fields, new_fields) = (labelled(old), labelled(new));
+    let field_path = |label: &str| format!("{}.{}", path, label.trim_start_matches('.'));
+    let is_pub = |field: &Field| field.vis == Visibility::Public;
+
+    for (label, old_field) in &old_fields {
+        let new_field = new_fields
+            .iter()
+            .find(|(new_label, _)| new_label == label)
+            .map(|(_, field)| field);
+        let (kind, impact) = match new_field {
+            None if is_pub(old_field) => (ApiChangeKind::FieldRemoved, Impact::Major),
+            Some(new_field) if is_pub(old_field) && !is_pub(new_field) => {
+                (ApiChangeKind::FieldMadePrivate, Impact::Major)
+            }
+            Some(new_field) if !is_pub(old_field) && is_pub(new_field) => {
+                (ApiChangeKind::FieldMadePublic, Impact::Minor)
+            }
+            Some(new_field)
+                if is_pub(old_field) && qualified(old_field) != qualified(new_field) =>
+            {
+                changes.push(
+                    ApiChange::new(
+                        field_path(label),
+                        ApiChangeKind::FieldTypeChanged,
+                        Impact::Major,
+                    )
+                    .with_detail(format!(
+                        "{} -> {}",
+                        qualified(old_field),
+                        qualified(new_field)
+                    )),
+                );
+                continue;
+            }
+            _ => continue,
+        };
+        changes.push(ApiChange::new(field_path(label), kind, impact));
+    }
+
+    // With only pub fields the struct can be built and matched exhaustively outside the crate
+    let constructible = !non_exhaustive && old.fields.iter().all(is_pub);
+    for (label, new_field) in &new_fields {
+        if old_fields.iter().any(|(old_label, _)| old_label == label) {
+            continue;
+        }
+        let impact = match (constructible, is_pub(new_field)) {
+            (true, _) => Impact::Major,
+            (false, true) => Impact::Minor,
+            (false, false) => continue,
+        };
+        changes.push(ApiChange::new(
+            field_path(label),
+            ApiChangeKind::FieldAdded,
+            impact,
+        ));
+    }
+}
+
+fn compare_variants(
+    path: &str,
+    old: &EnumElement,
+    new: &EnumElement,
+    non_exhaustive: bool,
+    changes: &mut Vec<ApiChange>,
+) {
+    let variant_path = |variant: &Variant| format!("{}::{}", path, variant.name);
+    for old_variant in &old.variants {
+        match new.variants.iter().find(|v| v.name == old_variant.name) {
+            None => changes.push(ApiChange::new(
+                variant_path(old_variant),
+                ApiChangeKind::VariantRemoved,
+                Impact::Major,
+            )),
+            // Compared as rendered, so that doc comments on the fields don't count
+            Some(new_variant) if variant_fields(new_variant) != variant_fields(old_variant) => {
+                changes.push(
+                    ApiChange::new(
+                        variant_path(old_variant),
+                        ApiChangeKind::VariantChanged,
+                        Impact::Major,
+                    )
+                    .with_detail(format!(
+                        "{} -> {}",
+                        variant_fields(old_variant),
+                        variant_fields(new_variant)
+                    )),
+                )
+            }
+            Some(_) => {}
+        }
+    }
+    for new_variant in &new.variants {
+        if !old.variants.iter().any(|v| v.name == new_variant.name) {
+            let impact = match non_exhaustive {
+                true => Impact::Minor,
+                false => Impact::Major,
+            };
+            changes.push(ApiChange::new(
+                variant_path(new_variant),
+                ApiChangeKind::VariantAdded,
+                impact,
+            ));
+        }
+    }
+}
+
+/// `(u32, String)` or `{ id: u32 }`, empty for unit variants
+fn variant_fields(variant: &Variant) -> String {
+    let named = variant.fields.iter().any(|field| field.name.is_some());
+    let fields: Vec<String> = variant
+        .fields
+        .iter()
+        .map(|field| match &field.name {
+            Some(name) => format!("{}: {}", name, qualified(field)),
+            None => qualified(field).to_string(),
+        })
+        .collect();
+    match (fields.is_empty(), named) {
+        (true, _) => "()".to_string(),
+        (false, true) => format!("{{ {} }}", fields.join(", ")),
+        (false, false) => format!("({})", fields.join(", ")),
+    }
+}
+
+/// The field type with the names written with a module prefix qualified again (`a::Id`), so
+/// that moving the type to another module is a change
+fn qualified(field: &Field) -> Type {
+    fn qualify(ty: &Type, paths: &mut Vec<&Vec<String>>) -> Type {
+        let mut path_of = |name: &str| {
+            paths
+                .iter()
+                .position(|path| path.last().is_some_and(|last| last == name))
+                .map(|position| paths.remove(position).join("::"))
+                .unwrap_or_else(|| name.to_string())
+        };
+        match ty {
+            Type::Simple(name) => Type::Simple(path_of(name)),
+            Type::Generic(name, args) => {
+                let name = path_of(name);
+                Type::Generic(name, args.iter().map(|arg| qualify(arg, paths)).collect())
+            }
+            Type::Vec(inner) => Type::Vec(Box::new(qualify(inner, paths))),
+            Type::Other(inner) => Type::Other(Box::new(qualify(inner, paths))),
+            Type::Pointer { mutable, inner } => Type::Pointer {
+                mutable: *mutable,
+                inner: Box::new(qualify(inner, paths)),
+            },
+            Type::Tuple(types) => Type::Tuple(types.iter().map(|ty| qualify(ty, paths)).collect()),
+            _ => ty.clone(),
+        }
+    }
+    qualify(&field.ty, &mut field.paths.iter().collect())
+}
+
+impl ApiChange {
+    fn new(path: String, kind: ApiChangeKind, impact: Impact) -> Self {
+        ApiChange {
+            path,
+            kind,
+            impact,
+            detail: None,
+        }
+    }
+
+    fn with_detail(mut self, detail: String) -> Self {
+        self.detail = Some(detail);
+        self
+    }
+}
+
+impl fmt::Display for Impact {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Impact::Patch => write!(f, "patch"),
+            Impact::Minor => write!(f, "minor"),
+            Impact::Major => write!(f, "major"),
+        }
+    }
+}
+
+impl fmt::Display for ApiChangeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let description = match self {
+            ApiChangeKind::TypeAdded => "public type added",
+            ApiChangeKind::TypeRemoved => "public type removed",
+            ApiChangeKind::TypeMadePublic => "type made public",
+            ApiChangeKind::TypeMadePrivate => "type no longer public",
+            ApiChangeKind::KindChanged => "kind of type changed",
+            ApiChangeKind::FieldAdded => "field added",
+            ApiChangeKind::FieldRemoved => "pub field removed",
+            ApiChangeKind::FieldMadePublic => "field made pub",
+            ApiChangeKind::FieldMadePrivate => "field no longer pub",
+            ApiChangeKind::FieldTypeChanged => "field type changed",
+            ApiChangeKind::VariantAdded => "variant added",
+            ApiChangeKind::VariantRemoved => "variant removed",
+            ApiChangeKind::VariantChanged => "variant fields changed",
+            ApiChangeKind::NonExhaustiveAdded => "#[non_exhaustive] added",
+            ApiChangeKind::NonExhaustiveRemoved => "#[non_exhaustive] removed",
+        };
+        write!(f, "{}", description)
+    }
+}
+
+/// `major: Order.total: field type changed (u32 -> Money)`
+impl fmt::Display for ApiChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}: {}", self.impact, self.path, self.kind)?;
+        if let Some(detail) = &self.detail {
+            write!(f, " ({})", detail)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::funcs::*;
+
+    const OLD_CODE: &str = "
+        pub struct Order {
+            pub id: u64,
+            pub total: u32,
+            pub note: String,
+            secret: u8,
+        }
+
+        pub struct Point {
+            pub x: i32,
+        }
+
+        pub enum Status {
+            Open,
+            Closed(u32),
+        }
+
+        #[non_exhaustive]
+        pub enum Event {
+            Placed,
+        }
+
+        struct Internal {}
+    ";
+
+    const NEW_CODE: &str = "
+        pub struct Order {
+            pub id: u64,
+            pub total: Money,
+            pub secret: u8,
+            pub status: Status,
+        }
+
+        pub struct Point {
+            pub x: i32,
+            pub y: i32,
+        }
+
+        pub enum Status {
+            Open,
+            Closed(u32, String),
+            Cancelled,
+        }
+
+        #[non_exhaustive]
+        pub enum Event {
+            Placed,
+            Shipped,
+        }
+
+        pub struct Internal {}
+        pub struct Money {}
+    ";
+
+    #[test]
+    fn test_api_changes() {
+        let old = collect_model(parse_rust_code(OLD_CODE));
+        let new = collect_model(parse_rust_code(NEW_CODE));
+        let changes: Vec<String> = api_changes(&old, &new)
+            .iter()
+            .map(ApiChange::to_string)
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                "major: Order.total: field type changed (u32 -> Money)",
+                "major: Order.note: pub field removed",
+                "minor: Order.secret: field made pub",
+                "minor: Order.status: field added",
+                "major: Point.y: field added",
+                "major: Status::Closed: variant fields changed ((u32) -> (u32, String))",
+                "major: Status::Cancelled: variant added",
+                "minor: Event::Shipped: variant added",
+                "minor: Internal: type made public",
+                "minor: Money: public type added",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_required_bump() {
+        let old = collect_model(parse_rust_code(OLD_CODE));
+        let new = collect_model(parse_rust_code(NEW_CODE));
+        assert_eq!(required_bump(&api_changes(&old, &new)), Impact::Major);
+        assert_eq!(required_bump(&api_changes(&old, &old)), Impact::Patch);
+
+        let removed = collect_model(parse_rust_code("pub struct Order {}"));
+        let changes = api_changes(&removed, &collect_model(parse_rust_code("")));
+        assert_eq!(changes[0].kind, ApiChangeKind::TypeRemoved);
+        assert!(Impact::Minor < Impact::Major);
+    }
+
+    #[test]
+    fn test_type_moved_between_modules() {
+        let code = |ty: &str| {
+            format!(
+                "
+                pub mod a {{
+                    pub struct Id {{}}
+                }}
+                pub mod b {{
+                    pub struct Id {{}}
+                }}
+                pub struct Order {{
+                    pub id: {},
+                }}
+                pub enum Event {{
+                    Placed(Vec<{}>),
+                }}
+                ",
+                ty, ty
+            )
+        };
+        let old = collect_model(parse_rust_code(&code("a::Id")));
+        let new = collect_model(parse_rust_code(&code("b::Id")));
+        let changes: Vec<String> = api_changes(&old, &new)
+            .iter()
+            .map(ToString::to_string)
+            .collect();
+
+        assert_eq!(
+            changes,
+            vec![
+                "major: Order.id: field type changed (a::Id -> b::Id)",
+                "major: Event::Placed: variant fields changed ((Vec<a::Id>) -> (Vec<b::Id>))",
+            ]
+        );
+        assert!(api_changes(&old, &old).is_empty());
+    }
+}
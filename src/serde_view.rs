@@ -0,0 +1,488 @@
+use crate::structures::*;
+use syn::punctuated::Punctuated;
+use syn::{Expr, ExprLit, Lit, Meta, Token};
+
+/// The `#[serde(...)]` attributes the serde view understands, merged from every `serde` attribute
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct SerdeAttrs {
+    /// `rename = "x"`, or the `serialize` name of `rename(serialize = "x", deserialize = "y")`
+    pub rename: Option<String>,
+    pub rename_all: Option<String>,
+    pub rename_all_fields: Option<String>,
+    /// `skip` and `skip_serializing`
+    pub skip: bool,
+    pub flatten: bool,
+    pub tag: Option<String>,
+    pub content: Option<String>,
+    pub untagged: bool,
+}
+
+/// How an enum is represented, see <https://serde.rs/enum-representations.html>
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Tagging {
+    /// `{"Variant": {...}}`, the default
+    External,
+    /// `{"type": "Variant", ...}`
+    Internal {
+        tag: String,
+    },
+    /// `{"t": "Variant", "c": {...}}`
+    Adjacent {
+        tag: String,
+        content: String,
+    },
+    Untagged,
+}
+
+impl From<&Attributes> for SerdeAttrs {
+    fn from(attrs: &Attributes) -> Self {
+        let mut serde = SerdeAttrs::default();
+        let nested = attrs
+            .other
+            .iter()
+            .filter_map(|attr| match syn::parse_str::<Meta>(attr) {
+                Ok(Meta::List(list)) if list.path.is_ident("serde") => list
+                    .parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+                    .ok(),
+                _ => None,
+            })
+            .flatten();
+        for meta in nested {
+            let key = meta
+                .path()
+                .get_ident()
+                .map(|ident| ident.to_string())
+                .unwrap_or_default();
+            match (key.as_str(), &meta) {
+                ("skip" | "skip_serializing", Meta::Path(_)) => serde.skip = true,
+                ("flatten", Meta::Path(_)) => serde.flatten = true,
+                ("untagged", Meta::Path(_)) => serde.untagged = true,
+                ("rename", _) => serde.rename = string_value(&meta),
+                ("rename_all", _) => serde.rename_all = string_value(&meta),
+                ("rename_all_fields", _) => serde.rename_all_fields = string_value(&meta),
+                ("tag", _) => serde.tag = string_value(&meta),
+                ("content", _) => serde.content = string_value(&meta),
+                _ => {}
+            }
+        }
+        serde
+    }
+}
+
+/// `key = "value"`, or the `serialize` part of `key(serialize = "a", deserialize = "b")`
+fn string_value(meta: &Meta) -> Option<String> {
+    match meta {
+        Meta::NameValue(name_value) => match &name_value.value {
+            Expr::Lit(ExprLit {
+                lit: Lit::Str(value),
+                ..
+            }) => Some(value.value()),
+            _ => None,
+        },
+        Meta::List(list) => list
+            .parse_args_with(Punctuated::<Meta, Token![,]>::parse_terminated)
+            .ok()?
+            .iter()
+            .find(|nested| nested.path().is_ident("serialize"))
+            .and_then(string_value),
+        Meta::Path(_) => None,
+    }
+}
+
+impl SerdeAttrs {
+    pub fn tagging(&self) -> Tagging {
+        match (&self.tag, &self.content) {
+            _ if self.untagged => Tagging::Untagged,
+            (Some(tag), Some(content)) => Tagging::Adjacent {
+                tag: tag.clone(),
+                content: content.clone(),
+            },
+            (Some(tag), None) => Tagging::Internal { tag: tag.clone() },
+            _ => Tagging::External,
+        }
+    }
+}
+
+/// Applies a `rename_all` rule to a variant name written in PascalCase
+pub fn rename_variant(name: &str, rule: &str) -> String {
+    let snake = || {
+        let mut snake = String::new();
+        for (position, c) in name.char_indices() {
+            if position > 0 && c.is_uppercase() {
+                snake.push('_');
+            }
+            snake.extend(c.to_lowercase());
+        }
+        snake
+    };
+    match rule {
+        "lowercase" => name.to_lowercase(),
+        "UPPERCASE" => name.to_uppercase(),
+        "camelCase" => lowercase_first(name),
+        "snake_case" => snake(),
+        "SCREAMING_SNAKE_CASE" => snake().to_uppercase(),
+        "kebab-case" => snake().replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => snake().to_uppercase().replace('_', "-"),
+        _ => name.to_string(),
+    }
+}
+
+/// Applies a `rename_all` rule to a field name written in snake_case
+pub fn rename_field(name: &str, rule: &str) -> String {
+    let pascal = || {
+        name.split('_')
+            .map(|word| {
+                let mut chars = word.chars();
+                match chars.next() {
+                    Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+                    None => String::new(),
+                }
+            })
+            .collect::<String>()
+    };
+    match rule {
+        "UPPERCASE" | "SCREAMING_SNAKE_CASE" => name.to_uppercase(),
+        "PascalCase" => pascal(),
+        "camelCase" => lowercase_first(&pascal()),
+        "kebab-case" => name.replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => name.to_uppercase().replace('_', "-"),
+        _ => name.to_string(),
+    }
+}
+
+fn lowercase_first(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().collect::<String>() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+/// The model as it goes over the wire: serde renames applied to fields and variants, skipped
+/// ones removed, `flatten` fields replaced by the fields of the local struct they hold, and the
+/// tag of internally or adjacently tagged enums added to every variant as a field holding the
+/// variant's tag value (`Type::Literal`), after the field of an internally tagged newtype
+/// variant. Adjacently tagged variants hold the rest under the content name: `c.field` for
+/// struct variants, `c` for newtype and tuple variants. Type names stay as in Rust, so edges
+/// are unaffected
+pub fn serde_view(model: &Model) -> Model {
+    wire_model(model, true)
+}
+
+/// `serde_view`, optionally without the tag fields, for consumers that handle tagging themselves
+pub(crate) fn wire_model(model: &Model, tag_fields: bool) -> Model {
+    let elements = model
+        .elements
+        .iter()
+        .map(|element| match element {
+            Element::Struct(struct_element) => {
+                let mut wire = struct_element.clone();
+                wire.fields = wire_fields(model, struct_element, 0);
+                Element::Struct(wire)
+            }
+            Element::Enum(enum_element) => {
+                Element::Enum(wire_enum(model, enum_element, tag_fields))
+            }
+            Element::Trait(trait_element) => Element::Trait(trait_element.clone()),
+        })
+        .collect();
+
+    Model {
+        modules: model.modules.clone(),
+        elements,
+    }
+}
+
+/// Flattening is followed this many structs deep, to stop on recursive `flatten`
+const MAX_FLATTEN_DEPTH: usize = 8;
+
+fn wire_fields(model: &Model, struct_element: &StructElement, depth: usize) -> Vec<Field> {
+    let container = SerdeAttrs::from(&struct_element.attrs);
+    apply_to_fields(
+        model,
+        &struct_element.fields,
+        container.rename_all.as_deref(),
+        depth,
+    )
+}
+
+fn apply_to_fields(
+    model: &Model,
+    fields: &[Field],
+    rename_all: Option<&str>,
+    depth: usize,
+) -> Vec<Field> {
+    let mut wire = Vec::new();
+    for field in fields {
+        let serde = SerdeAttrs::from(&field.attrs);
+        if serde.skip {
+            continue;
+        }
+        if serde.flatten && depth < MAX_FLATTEN_DEPTH {
+            if let Some(flattened) = flattened_struct(model, &field.ty) {
+                wire.extend(wire_fields(model, flattened, depth + 1));
+                continue;
+            }
+        }
+        let mut field = field.clone();
+        if let Some(name) = &field.name {
+            let name = name.trim_start_matches("r#");
+            field.name = Some(match (&serde.rename, rename_all) {
+                (Some(rename), _) => rename.clone(),
+                (None, Some(rule)) => rename_field(name, rule),
+                (None, None) => name.to_string(),
+            });
+        }
+        wire.push(field);
+    }
+    wire
+}
+
+/// Local struct behind a `flatten` field, also through `Option<T>` and `Box<T>`
+fn flattened_struct<'a>(model: &'a Model, ty: &Type) -> Option<&'a StructElement> {
+    match ty {
+        Type::Simple(name) => model.elements.iter().find_map(|element| match element {
+            Element::Struct(struct_element) if &struct_element.name == name => Some(struct_element),
+            _ => None,
+        }),
+        Type::Generic(wrapper, args)
+            if args.len() == 1 && (wrapper == "Option" || wrapper == "Box") =>
+        {
+            flattened_struct(model, &args[0])
+        }
+        _ => None,
+    }
+}
+
+fn wire_enum(model: &Model, enum_element: &EnumElement, tag_fields: bool) -> EnumElement {
+    let container = SerdeAttrs::from(&enum_element.attrs);
+    let tagging = container.tagging();
+    let mut wire = enum_element.clone();
+    wire.variants = enum_element
+        .variants
+        .iter()
+        .filter_map(|variant| {
+            let serde = SerdeAttrs::from(&variant.attrs);
+            if serde.skip {
+                return None;
+            }
+            let name = match (&serde.rename, &container.rename_all) {
+                (Some(rename), _) => rename.clone(),
+                (None, Some(rule)) => rename_variant(&variant.name, rule),
+                (None, None) => variant.name.clone(),
+            };
+            let rename_all = serde
+                .rename_all
+                .as_deref()
+                .or(container.rename_all_fields.as_deref());
+            let mut fields = apply_to_fields(model, &variant.fields, rename_all, 0);
+            let tag_field = |tag: &str| Field {
+                name: Some(tag.to_string()),
+                vis: Visibility::Public,
+                attrs: Attributes::default(),
+                ty: Type::Literal(name.clone()),
+                paths: Vec::new(),
+            };
+            match &tagging {
+                _ if !tag_fields => {}
+                // Serde puts the tag next to the fields of a newtype's struct, the field itself
+                // keeps its position
+                Tagging::Internal { tag } => match fields.first() {
+                    Some(field) if field.name.is_none() => fields.push(tag_field(tag)),
+                    _ => fields.insert(0, tag_field(tag)),
+                },
+                // The content of a newtype variant is its value, of a tuple variant an array
+                Tagging::Adjacent { tag, content } => {
+                    match fields.first().map(|field| field.name.is_some()) {
+                        Some(true) => {
+                            for field in &mut fields {
+                                let name = field.name.take().unwrap_or_default();
+                                field.name = Some(format!("{}.{}", content, name));
+                            }
+                        }
+                        Some(false) => {
+                            let mut value = fields.remove(0);
+                            if !fields.is_empty() {
+                                let mut types = vec![value.ty];
+                                for field in fields.drain(..) {
+                                    types.push(field.ty);
+                                    value.paths.extend(field.paths);
+                                }
+                                value.ty = Type::Tuple(types);
+                                value.attrs = Attributes::default();
+                            }
+                            value.name = Some(content.clone());
+                            fields.push(value);
+                        }
+                        None => {}
+                    }
+                    fields.insert(0, tag_field(tag));
+                }
+                Tagging::External | Tagging::Untagged => {}
+            }
+            Some(Variant {
+                name,
+                attrs: variant.attrs.clone(),
+                fields,
+            })
+        })
+        .collect();
+    wire
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::export::members;
+    use crate::funcs::*;
+    use petgraph::graph::DiGraph;
+
+    #[test]
+    fn test_serde_view_struct() {
+        let code = r#"
+            #[derive(Serialize)]
+            #[serde(rename_all = "camelCase")]
+            pub struct Order {
+                pub order_id: u64,
+                #[serde(rename = "sum")]
+                pub total_amount: u32,
+                #[serde(skip)]
+                pub cache: Cache,
+                #[serde(flatten)]
+                pub meta: Meta,
+                pub r#type: String,
+            }
+
+            #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+            pub struct Meta {
+                pub created_at: u64,
+            }
+
+            pub struct Cache {}
+        "#;
+        let model = serde_view(&collect_model(parse_rust_code(code)));
+
+        assert_eq!(
+            members(&model.elements[0]),
+            vec![
+                "+ orderId: u64",
+                "+ sum: u32",
+                "+ CREATED_AT: u64",
+                "+ type: String"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_serde_view_enum_tagging() {
+        let code = r#"
+            #[serde(tag = "kind", rename_all = "snake_case")]
+            pub enum Shape {
+                Circle { radius: f64 },
+                UnitSquare,
+                #[serde(skip)]
+                Hidden,
+            }
+
+            #[serde(tag = "t", content = "c")]
+            pub enum Message {
+                #[serde(rename = "ping")]
+                Ping(u64),
+                Move(u8, u8),
+                Data { payload_size: u32 },
+            }
+
+            #[serde(untagged)]
+            pub enum Value {
+                Number(f64),
+            }
+        "#;
+        let model = serde_view(&collect_model(parse_rust_code(code)));
+
+        assert_eq!(
+            members(&model.elements[0]),
+            vec![
+                "circle { kind: \"circle\", radius: f64 }",
+                "unit_square { kind: \"unit_square\" }"
+            ]
+        );
+        assert_eq!(model.elements[0].name(), "Shape");
+        match &model.elements[0] {
+            Element::Enum(shape) => {
+                assert_eq!(shape.variants[0].name, "circle");
+                assert_eq!(shape.variants[1].name, "unit_square");
+            }
+            _ => panic!("Expected Element::Enum"),
+        }
+        assert_eq!(
+            members(&model.elements[1]),
+            vec![
+                "ping { t: \"ping\", c: u64 }",
+                "Move { t: \"Move\", c: (u8, u8) }",
+                "Data { t: \"Data\", c.payload_size: u32 }"
+            ]
+        );
+        assert_eq!(members(&model.elements[2]), vec!["Number(f64)"]);
+    }
+
+    const TAGGED_NEWTYPE: &str = r#"
+        #[serde(tag = "type")]
+        pub enum Shape {
+            Circle { radius: f64 },
+            Square(Side),
+        }
+
+        pub struct Side {
+            pub length: f64,
+        }
+    "#;
+
+    #[test]
+    fn test_serde_view_internally_tagged_newtype() {
+        let model = serde_view(&collect_model(parse_rust_code(TAGGED_NEWTYPE)));
+
+        assert_eq!(
+            members(&model.elements[0]),
+            vec![
+                "Circle { type: \"Circle\", radius: f64 }",
+                "Square { .0: Side, type: \"Square\" }"
+            ]
+        );
+        let mut graph = DiGraph::<String, Edge>::new();
+        let node_indices = create_nodes_for_graph(&model.elements, &mut graph);
+        add_edges_for_graph(&model.elements, &mut graph, &node_indices);
+        let edge = graph.find_edge(node_indices[0], node_indices[1]).unwrap();
+        assert_eq!(graph[edge].field.as_deref(), Some("Square.0"));
+    }
+
+    #[test]
+    fn test_serde_view_tag_is_not_a_node() {
+        let model = serde_view(&collect_model(parse_rust_code(TAGGED_NEWTYPE)));
+        let options = GraphOptions {
+            external_types: true,
+            primitive_types: true,
+            ..GraphOptions::default()
+        };
+        let mut graph = DiGraph::<String, Edge>::new();
+        let node_indices = create_nodes_for_graph(&model.elements, &mut graph);
+        add_edges_for_graph_with_options(&model.elements, &mut graph, &node_indices, &options);
+
+        let labels: Vec<&String> = graph.node_weights().collect();
+        assert_eq!(labels, vec!["Shape", "Side", "f64"]);
+        assert_eq!(graph.edge_count(), 3);
+    }
+
+    #[test]
+    fn test_rename_rules() {
+        assert_eq!(rename_variant("UnitSquare", "kebab-case"), "unit-square");
+        assert_eq!(rename_variant("UnitSquare", "camelCase"), "unitSquare");
+        assert_eq!(rename_variant("Éclair", "camelCase"), "éclair");
+        assert_eq!(rename_variant("CrèmeBrûlée", "snake_case"), "crème_brûlée");
+        assert_eq!(rename_field("created_at", "PascalCase"), "CreatedAt");
+        assert_eq!(
+            rename_field("created_at", "SCREAMING-KEBAB-CASE"),
+            "CREATED-AT"
+        );
+        assert_eq!(rename_field("été_count", "camelCase"), "étéCount");
+    }
+}
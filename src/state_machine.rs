@@ -0,0 +1,418 @@
+use crate::impls::tokens_to_string;
+use crate::structures::*;
+use syn::visit::{self, Visit};
+use syn::{Arm, Block, Expr, ExprIf, ExprMatch, File, ImplItemFn, ItemFn, ItemImpl, Pat, Path};
+
+/// States and transitions of an enum, found in the function bodies that match on it
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct StateMachine {
+    pub name: String,
+    /// Every variant, in declaration order
+    pub states: Vec<String>,
+    /// `#[default]` variant, or the variants built in `impl Default`
+    pub initial: Vec<String>,
+    pub transitions: Vec<Transition>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Transition {
+    pub from: String,
+    pub to: String,
+    /// Function the transition happens in, with the other parts of a tuple pattern
+    /// (`handle(Event::Start)` for `(State::Idle, Event::Start) => ...`)
+    pub label: String,
+}
+
+/// Scans `files` for `match` and `if let` on the variants of `enum_name`. Every variant built in
+/// an arm (`self.state = State::B`, `State::B` as the arm value, `return State::B(x)`) is a
+/// transition from the variants of its pattern; `_` stands for the variants no other arm names.
+/// Only qualified paths are recognized: `State::A`, or `Self::A` inside `impl State`
+pub fn state_machine(model: &Model, files: &[File], enum_name: &str) -> Option<StateMachine> {
+    let enum_element = model.elements.iter().find_map(|element| match element {
+        Element::Enum(enum_element) if enum_element.name == enum_name => Some(enum_element),
+        _ => None,
+    })?;
+    let states: Vec<String> = enum_element
+        .variants
+        .iter()
+        .map(|variant| variant.name.clone())
+        .collect();
+
+    let mut finder = TransitionFinder {
+        enum_name,
+        states: &states,
+        self_is_enum: false,
+        in_default: false,
+        function: None,
+        initial: enum_element
+            .variants
+            .iter()
+            .filter(|variant| variant.attrs.other.iter().any(|attr| attr == "default"))
+            .map(|variant| variant.name.clone())
+            .collect(),
+        transitions: Vec::new(),
+    };
+    for file in files {
+        finder.visit_file(file);
+    }
+
+    Some(StateMachine {
+        name: enum_name.to_string(),
+        states: states.clone(),
+        initial: finder.initial,
+        transitions: finder.transitions,
+    })
+}
+
+struct TransitionFinder<'a> {
+    enum_name: &'a str,
+    states: &'a [String],
+    /// Inside `impl EnumName`, where `Self::A` is a state
+    self_is_enum: bool,
+    /// Inside `impl Default for EnumName`
+    in_default: bool,
+    function: Option<String>,
+    initial: Vec<String>,
+    transitions: Vec<Transition>,
+}
+
+impl TransitionFinder<'_> {
+    fn state_of(&self, path: &Path) -> Option<String> {
+        let segments: Vec<String> = path.segments.iter().map(|s| s.ident.to_string()).collect();
+        let [.., owner, variant] = segments.as_slice() else {
+            return None;
+        };
+        let owned = owner == self.enum_name || (owner == "Self" && self.self_is_enum);
+        (owned && self.states.contains(variant)).then(|| variant.clone())
+    }
+
+    /// States a pattern matches, and the text of the tuple elements that are not states
+    fn pattern_states(&self, pat: &Pat, states: &mut Vec<String>, others: &mut Vec<String>) {
+        match pat {
+            Pat::Path(pat_path) => states.extend(self.state_of(&pat_path.path)),
+            Pat::TupleStruct(tuple_struct) => states.extend(self.state_of(&tuple_struct.path)),
+            Pat::Struct(pat_struct) => states.extend(self.state_of(&pat_struct.path)),
+            Pat::Or(pat_or) => {
+                for case in &pat_or.cases {
+                    self.pattern_states(case, states, others);
+                }
+            }
+            Pat::Tuple(tuple) => {
+                for elem in &tuple.elems {
+                    let found = states.len();
+                    self.pattern_states(elem, states, &mut Vec::new());
+                    if states.len() == found && !matches!(elem, Pat::Wild(_)) {
+                        others.push(tokens_to_string(elem));
+                    }
+                }
+            }
+            Pat::Reference(reference) => self.pattern_states(&reference.pat, states, others),
+            Pat::Paren(paren) => self.pattern_states(&paren.pat, states, others),
+            Pat::Ident(ident) => {
+                if let Some((_, subpat)) = &ident.subpat {
+                    self.pattern_states(subpat, states, others);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn built_states(&self, expr: &Expr) -> Vec<String> {
+        let mut builder = StateBuilds {
+            finder: self,
+            built: Vec::new(),
+        };
+        builder.visit_expr(expr);
+        builder.built
+    }
+
+    fn add_transitions(&mut self, from: &[String], others: &[String], to: &[String]) {
+        let function = self.function.clone().unwrap_or_default();
+        let label = if others.is_empty() {
+            function
+        } else {
+            format!("{}({})", function, others.join(", "))
+        };
+        for from in from {
+            for to in to {
+                let transition = Transition {
+                    from: from.clone(),
+                    to: to.clone(),
+                    label: label.clone(),
+                };
+                if !self.transitions.contains(&transition) {
+                    self.transitions.push(transition);
+                }
+            }
+        }
+    }
+
+    fn visit_function(&mut self, name: String, block: &Block, visit: impl FnOnce(&mut Self)) {
+        let outer = self.function.replace(name);
+        if self.in_default {
+            let mut builder = StateBuilds {
+                finder: self,
+                built: Vec::new(),
+            };
+            builder.visit_block(block);
+            for state in builder.built {
+                if !self.initial.contains(&state) {
+                    self.initial.push(state);
+                }
+            }
+        }
+        visit(self);
+        self.function = outer;
+    }
+}
+
+impl<'ast> Visit<'ast> for TransitionFinder<'_> {
+    fn visit_item_impl(&mut self, item_impl: &'ast ItemImpl) {
+        let self_is_enum = match item_impl.self_ty.as_ref() {
+            syn::Type::Path(type_path) => type_path
+                .path
+                .segments
+                .last()
+                .is_some_and(|segment| segment.ident == self.enum_name),
+            _ => false,
+        };
+        let is_default = item_impl
+            .trait_
+            .as_ref()
+            .and_then(|(_, path, _)| path.segments.last())
+            .is_some_and(|segment| segment.ident == "Default");
+        let outer = (self.self_is_enum, self.in_default);
+        self.self_is_enum = self_is_enum;
+        self.in_default = self_is_enum && is_default;
+        visit::visit_item_impl(self, item_impl);
+        (self.self_is_enum, self.in_default) = outer;
+    }
+
+    fn visit_impl_item_fn(&mut self, function: &'ast ImplItemFn) {
+        self.visit_function(function.sig.ident.to_string(), &function.block, |finder| {
+            visit::visit_impl_item_fn(finder, function)
+        });
+    }
+
+    fn visit_item_fn(&mut self, function: &'ast ItemFn) {
+        self.visit_function(function.sig.ident.to_string(), &function.block, |finder| {
+            visit::visit_item_fn(finder, function)
+        });
+    }
+
+    fn visit_expr_match(&mut self, expr_match: &'ast ExprMatch) {
+        let mut named = Vec::new();
+        let arms: Vec<(&Arm, Vec<String>, Vec<String>)> = expr_match
+            .arms
+            .iter()
+            .map(|arm| {
+                let (mut states, mut others) = (Vec::new(), Vec::new());
+                self.pattern_states(&arm.pat, &mut states, &mut others);
+                named.extend(states.iter().cloned());
+                (arm, states, others)
+            })
+            .collect();
+        if !named.is_empty() {
+            let unnamed: Vec<String> = self
+                .states
+                .iter()
+                .filter(|state| !named.contains(state))
+                .cloned()
+                .collect();
+            for (arm, states, others) in arms {
+                let catch_all = matches!(&arm.pat, Pat::Wild(_))
+                    || matches!(&arm.pat, Pat::Ident(ident) if ident.subpat.is_none());
+                let from = if states.is_empty() && catch_all {
+                    unnamed.clone()
+                } else {
+                    states
+                };
+                let to = self.built_states(&arm.body);
+                self.add_transitions(&from, &others, &to);
+            }
+        }
+        visit::visit_expr_match(self, expr_match);
+    }
+
+    fn visit_expr_if(&mut self, expr_if: &'ast ExprIf) {
+        if let Expr::Let(expr_let) = expr_if.cond.as_ref() {
+            let (mut states, mut others) = (Vec::new(), Vec::new());
+            self.pattern_states(&expr_let.pat, &mut states, &mut others);
+            if !states.is_empty() {
+                let mut builder = StateBuilds {
+                    finder: self,
+                    built: Vec::new(),
+                };
+                builder.visit_block(&expr_if.then_branch);
+                let to = builder.built;
+                self.add_transitions(&states, &others, &to);
+            }
+        }
+        visit::visit_expr_if(self, expr_if);
+    }
+}
+
+/// Variants of the enum built in an expression: `State::A`, `State::A(x)`, `State::A { .. }`
+struct StateBuilds<'f, 'a> {
+    finder: &'f TransitionFinder<'a>,
+    built: Vec<String>,
+}
+
+impl<'ast> Visit<'ast> for StateBuilds<'_, '_> {
+    fn visit_path(&mut self, path: &'ast Path) {
+        if let Some(state) = self.finder.state_of(path) {
+            if !self.built.contains(&state) {
+                self.built.push(state);
+            }
+        }
+    }
+
+    /// Patterns only test the state, they don't build one
+    fn visit_pat(&mut self, _: &'ast Pat) {}
+
+    /// `self.state == State::A` compares
+    fn visit_expr_binary(&mut self, _: &'ast syn::ExprBinary) {}
+}
+
+pub fn state_machine_to_mermaid(machine: &StateMachine) -> String {
+    let mut output = String::from("stateDiagram-v2\n");
+    for state in &machine.states {
+        output.push_str(&format!("    {}\n", state));
+    }
+    for initial in &machine.initial {
+        output.push_str(&format!("    [*] --> {}\n", initial));
+    }
+    for transition in &machine.transitions {
+        output.push_str(&format!("    {} --> {}", transition.from, transition.to));
+        if !transition.label.is_empty() {
+            output.push_str(&format!(" : {}", transition.label));
+        }
+        output.push('\n');
+    }
+    output
+}
+
+pub fn state_machine_to_dot(machine: &StateMachine) -> String {
+    let mut output = String::from("digraph {\n    node [ shape = Mrecord ]\n");
+    if !machine.initial.is_empty() {
+        output.push_str("    initial [ shape = point label = \"\" ]\n");
+    }
+    for (index, state) in machine.states.iter().enumerate() {
+        output.push_str(&format!("    {} [ label = \"{}\" ]\n", index, state));
+    }
+    let node = |name: &str| machine.states.iter().position(|state| state == name);
+    for initial in machine.initial.iter().filter_map(|state| node(state)) {
+        output.push_str(&format!("    initial -> {}\n", initial));
+    }
+    for transition in &machine.transitions {
+        if let (Some(from), Some(to)) = (node(&transition.from), node(&transition.to)) {
+            output.push_str(&format!(
+                "    {} -> {} [ label = \"{}\" ]\n",
+                from,
+                to,
+                transition.label.replace('"', "\\\"")
+            ));
+        }
+    }
+    output.push_str("}\n");
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::funcs::*;
+
+    const TEST_CODE: &str = "
+        #[derive(Default)]
+        pub enum State {
+            #[default]
+            Idle,
+            Running { pid: u32 },
+            Stopped(i32),
+            Failed,
+        }
+
+        pub enum Event {
+            Start,
+            Exit(i32),
+        }
+
+        pub struct Machine {
+            state: State,
+        }
+
+        impl Machine {
+            fn start(&mut self) {
+                match self.state {
+                    State::Idle | State::Stopped(_) => self.state = State::Running { pid: 1 },
+                    State::Running { .. } => {}
+                    _ => self.state = State::Failed,
+                }
+            }
+
+            fn handle(&mut self, event: Event) {
+                self.state = match (&self.state, event) {
+                    (State::Running { .. }, Event::Exit(code)) => State::Stopped(code),
+                    (state, _) => return,
+                };
+            }
+
+            fn reset(&mut self) {
+                if let State::Failed = self.state {
+                    if self.state == State::Idle {}
+                    self.state = State::Idle;
+                }
+            }
+        }
+
+        impl State {
+            fn next(self) -> Self {
+                match self {
+                    Self::Stopped(_) => Self::Idle,
+                    other => other,
+                }
+            }
+        }
+    ";
+
+    #[test]
+    fn test_state_machine() {
+        let file = parse_rust_code(TEST_CODE);
+        let model = collect_model(file.clone());
+        let machine = state_machine(&model, &[file], "State").unwrap();
+
+        assert_eq!(machine.initial, vec!["Idle"]);
+        let transitions: Vec<(&str, &str, &str)> = machine
+            .transitions
+            .iter()
+            .map(|t| (t.from.as_str(), t.to.as_str(), t.label.as_str()))
+            .collect();
+        assert_eq!(
+            transitions,
+            vec![
+                ("Idle", "Running", "start"),
+                ("Stopped", "Running", "start"),
+                ("Failed", "Failed", "start"),
+                ("Running", "Stopped", "handle(Event::Exit(code))"),
+                ("Failed", "Idle", "reset"),
+                ("Stopped", "Idle", "next"),
+            ]
+        );
+        assert!(state_machine(&model, &[], "Machine").is_none());
+    }
+
+    #[test]
+    fn test_state_machine_export() {
+        let file = parse_rust_code(TEST_CODE);
+        let model = collect_model(file.clone());
+        let machine = state_machine(&model, &[file], "State").unwrap();
+
+        let mermaid = state_machine_to_mermaid(&machine);
+        assert!(mermaid.starts_with("stateDiagram-v2\n    Idle\n"));
+        assert!(mermaid.contains("    [*] --> Idle\n    Idle --> Running : start\n"));
+        let dot = state_machine_to_dot(&machine);
+        assert!(dot.contains("    initial -> 0\n"));
+        assert!(dot.contains("    3 -> 0 [ label = \"reset\" ]\n"));
+    }
+}
@@ -0,0 +1,396 @@
+use crate::funcs::*;
+use crate::module_graph::*;
+use crate::structures::*;
+use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::Direction;
+use serde_json::{json, Value};
+use std::cmp::Reverse;
+use std::collections::{HashSet, VecDeque};
+
+/// Metrics of the type graph, to track the complexity of a model over time
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Stats {
+    /// In model order
+    pub types: Vec<TypeStats>,
+    /// In model order
+    pub modules: Vec<ModuleStats>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TypeStats {
+    pub name: String,
+    pub module: Vec<String>,
+    /// `struct`, `enum` or `trait`
+    pub kind: &'static str,
+    /// Other types referring to this one
+    pub fan_in: usize,
+    /// Other types this one refers to
+    pub fan_out: usize,
+    /// Fewest edges from a root (a type nothing refers to), `None` when only cycles lead to it
+    pub depth: Option<usize>,
+    /// Struct fields, or the fields of all variants of an enum
+    pub fields: usize,
+    pub variants: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModuleStats {
+    pub path: Vec<String>,
+    pub types: usize,
+    /// Modules depending on this one (Ca)
+    pub afferent: usize,
+    /// Modules this one depends on (Ce)
+    pub efferent: usize,
+    /// Ce / (Ca + Ce): 0 for modules only depended upon, 1 for modules only depending on others
+    pub instability: f64,
+}
+
+/// Type metrics from the default type graph (any edge kind counts), module metrics from
+/// `module_graph`
+pub fn stats(model: &Model) -> Stats {
+    let mut graph = DiGraph::<String, Edge>::new();
+    let node_indices = create_nodes_for_graph(&model.elements, &mut graph);
+    let options = GraphOptions {
+        modules: model.modules.clone(),
+        ..GraphOptions::default()
+    };
+    add_edges_for_graph_with_options(&model.elements, &mut graph, &node_indices, &options);
+    let neighbors = |node: NodeIndex, direction: Direction| {
+        graph
+            .neighbors_directed(node, direction)
+            .filter(|&other| other != node && node_indices.contains(&other))
+            .collect::<HashSet<_>>()
+            .len()
+    };
+
+    // Breadth-first from every root at once
+    let mut depths: Vec<Option<usize>> = vec![None; graph.node_count()];
+    let mut queue = VecDeque::new();
+    for &node in &node_indices {
+        if neighbors(node, Direction::Incoming) == 0 {
+            depths[node.index()] = Some(0);
+            queue.push_back(node);
+        }
+    }
+    while let Some(node) = queue.pop_front() {
+        let depth = depths[node.index()].map(|depth| depth + 1);
+        for next in graph.neighbors(node) {
+            if depths[next.index()].is_none() {
+                depths[next.index()] = depth;
+                queue.push_back(next);
+            }
+        }
+    }
+
+    let types = model
+        .elements
+        .iter()
+        .zip(&node_indices)
+        .map(|(element, &node)| {
+            let (kind, fields, variants) = match element {
+                Element::Struct(struct_element) => ("struct", struct_element.fields.len(), 0),
+                Element::Enum(enum_element) => (
+                    "enum",
+                    enum_element.variants.iter().map(|v| v.fields.len()).sum(),
+                    enum_element.variants.len(),
+                ),
+                Element::Trait(_) => ("trait", 0, 0),
+            };
+            TypeStats {
+                name: element.name().to_string(),
+                module: element.module().to_vec(),
+                kind,
+                fan_in: neighbors(node, Direction::Incoming),
+                fan_out: neighbors(node, Direction::Outgoing),
+                depth: depths[node.index()],
+                fields,
+                variants,
+            }
+        })
+        .collect();
+
+    let module_graph = module_graph(model);
+    let modules = module_graph
+        .modules
+        .iter()
+        .map(|path| {
+            let afferent = module_graph.edges.iter().filter(|e| e.to == *path).count();
+            let efferent = module_graph
+                .edges
+                .iter()
+                .filter(|e| e.from == *path)
+                .count();
+            ModuleStats {
+                path: path.clone(),
+                types: model.elements.iter().filter(|e| e.module() == path).count(),
+                afferent,
+                efferent,
+                instability: match afferent + efferent {
+                    0 => 0.0,
+                    total => efferent as f64 / total as f64,
+                },
+            }
+        })
+        .collect();
+
+    Stats { types, modules }
+}
+
+impl Stats {
+    /// Up to `count` types with the highest fan-in, types nothing refers to are left out
+    pub fn most_depended_upon(&self, count: usize) -> Vec<&TypeStats> {
+        let mut types: Vec<&TypeStats> = self.types.iter().filter(|t| t.fan_in > 0).collect();
+        types.sort_by_key(|t| Reverse(t.fan_in));
+        types.truncate(count);
+        types
+    }
+}
+
+const TYPE_COLUMNS: [&str; 8] = [
+    "module", "type", "kind", "fan_in", "fan_out", "depth", "fields", "variants",
+];
+
+const MODULE_COLUMNS: [&str; 5] = ["module", "types", "afferent", "efferent", "instability"];
+
+/// How many types `stats_to_table` and `stats_to_json` list as most depended upon
+const MOST_DEPENDED_UPON: usize = 10;
+
+fn type_row(stats: &TypeStats) -> Vec<String> {
+    vec![
+        module_name(&stats.module),
+        stats.name.clone(),
+        stats.kind.to_string(),
+        stats.fan_in.to_string(),
+        stats.fan_out.to_string(),
+        stats.depth.map(|d| d.to_string()).unwrap_or_default(),
+        stats.fields.to_string(),
+        stats.variants.to_string(),
+    ]
+}
+
+fn module_row(stats: &ModuleStats) -> Vec<String> {
+    vec![
+        module_name(&stats.path),
+        stats.types.to_string(),
+        stats.afferent.to_string(),
+        stats.efferent.to_string(),
+        format!("{:.2}", stats.instability),
+    ]
+}
+
+/// Columns padded to their widest cell, numbers aligned right
+fn table(headers: &[&str], rows: &[Vec<String>]) -> String {
+    let widths: Vec<usize> = (0..headers.len())
+        .map(|column| {
+            rows.iter()
+                .map(|row| row[column].len())
+                .chain([headers[column].len()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    let line = |cells: Vec<&str>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(
+                |(cell, &width)| match cell.parse::<f64>().is_ok() || *cell == "-" {
+                    true => format!("{:>width$}", cell),
+                    false => format!("{:<width$}", cell),
+                },
+            )
+            .collect();
+        format!("{}\n", padded.join("  ").trim_end())
+    };
+    let mut output = line(headers.to_vec());
+    for row in rows {
+        output.push_str(&line(row.iter().map(String::as_str).collect()));
+    }
+    output
+}
+
+/// Types, modules and the most depended-upon types as aligned tables, a missing depth is `-`
+pub fn stats_to_table(stats: &Stats) -> String {
+    let types: Vec<Vec<String>> = stats
+        .types
+        .iter()
+        .map(|t| {
+            let mut row = type_row(t);
+            if t.depth.is_none() {
+                row[5] = "-".to_string();
+            }
+            row
+        })
+        .collect();
+    let modules: Vec<Vec<String>> = stats.modules.iter().map(module_row).collect();
+    let most: Vec<Vec<String>> = stats
+        .most_depended_upon(MOST_DEPENDED_UPON)
+        .iter()
+        .map(|t| vec![module_name(&t.module), t.name.clone(), t.fan_in.to_string()])
+        .collect();
+    format!(
+        "{}\n{}\nMost depended upon:\n{}",
+        table(&TYPE_COLUMNS, &types),
+        table(&MODULE_COLUMNS, &modules),
+        table(&["module", "type", "fan_in"], &most)
+    )
+}
+
+/// The types, then after an empty line the modules, each with a header row. A missing depth
+/// is an empty cell
+pub fn stats_to_csv(stats: &Stats) -> String {
+    let csv = |headers: &[&str], rows: Vec<Vec<String>>| {
+        let mut output = format!("{}\n", headers.join(","));
+        for row in rows {
+            output.push_str(&row.join(","));
+            output.push('\n');
+        }
+        output
+    };
+    format!(
+        "{}\n{}",
+        csv(&TYPE_COLUMNS, stats.types.iter().map(type_row).collect()),
+        csv(
+            &MODULE_COLUMNS,
+            stats.modules.iter().map(module_row).collect()
+        )
+    )
+}
+
+/// `{"types": [..], "modules": [..], "most_depended_upon": ["crate::Order", ..]}`
+pub fn stats_to_json(stats: &Stats) -> Value {
+    let types: Vec<Value> = stats
+        .types
+        .iter()
+        .map(|t| {
+            json!({
+                "module": module_name(&t.module),
+                "type": t.name,
+                "kind": t.kind,
+                "fan_in": t.fan_in,
+                "fan_out": t.fan_out,
+                "depth": t.depth,
+                "fields": t.fields,
+                "variants": t.variants,
+            })
+        })
+        .collect();
+    let modules: Vec<Value> = stats
+        .modules
+        .iter()
+        .map(|m| {
+            json!({
+                "module": module_name(&m.path),
+                "types": m.types,
+                "afferent": m.afferent,
+                "efferent": m.efferent,
+                "instability": m.instability,
+            })
+        })
+        .collect();
+    let most: Vec<String> = stats
+        .most_depended_upon(MOST_DEPENDED_UPON)
+        .iter()
+        .map(|t| format!("{}::{}", module_name(&t.module), t.name))
+        .collect();
+    json!({ "types": types, "modules": modules, "most_depended_upon": most })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_CODE: &str = "
+        pub struct Shop {
+            pub orders: Vec<domain::Order>,
+            pub customers: Vec<domain::Customer>,
+        }
+
+        pub mod domain {
+            pub struct Order {
+                pub customer: Customer,
+                pub status: Status,
+                pub total: u64,
+            }
+
+            pub struct Customer {}
+
+            pub enum Status {
+                Open,
+                Closed { at: u64, by: Customer },
+            }
+        }
+    ";
+
+    #[test]
+    fn test_stats() {
+        let stats = stats(&collect_model(parse_rust_code(TEST_CODE)));
+        let types: Vec<(&str, usize, usize, Option<usize>)> = stats
+            .types
+            .iter()
+            .map(|t| (t.name.as_str(), t.fan_in, t.fan_out, t.depth))
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                ("Shop", 0, 2, Some(0)),
+                ("Order", 1, 2, Some(1)),
+                ("Customer", 3, 0, Some(1)),
+                ("Status", 1, 1, Some(2)),
+            ]
+        );
+        let members: Vec<(usize, usize)> =
+            stats.types.iter().map(|t| (t.fields, t.variants)).collect();
+        assert_eq!(members, vec![(2, 0), (3, 0), (0, 0), (2, 2)]);
+
+        let modules: Vec<(String, usize, usize, usize, f64)> = stats
+            .modules
+            .iter()
+            .map(|m| {
+                let name = module_name(&m.path);
+                (name, m.types, m.afferent, m.efferent, m.instability)
+            })
+            .collect();
+        assert_eq!(
+            modules,
+            vec![
+                ("crate".to_string(), 1, 0, 1, 1.0),
+                ("domain".to_string(), 3, 1, 0, 0.0),
+            ]
+        );
+
+        let most: Vec<&str> = stats
+            .most_depended_upon(2)
+            .iter()
+            .map(|t| t.name.as_str())
+            .collect();
+        assert_eq!(most, vec!["Customer", "Order"]);
+    }
+
+    #[test]
+    fn test_stats_output() {
+        let stats = stats(&collect_model(parse_rust_code(TEST_CODE)));
+
+        let csv = stats_to_csv(&stats);
+        assert!(csv.starts_with(
+            "module,type,kind,fan_in,fan_out,depth,fields,variants\n\
+             crate,Shop,struct,0,2,0,2,0\n"
+        ));
+        assert!(csv.ends_with(
+            "\nmodule,types,afferent,efferent,instability\n\
+             crate,1,0,1,1.00\n\
+             domain,3,1,0,0.00\n"
+        ));
+
+        let table = stats_to_table(&stats);
+        assert!(table.starts_with(
+            "module  type      kind    fan_in  fan_out  depth  fields  variants\n\
+             crate   Shop      struct       0        2      0       2         0\n"
+        ));
+
+        let json = stats_to_json(&stats);
+        assert_eq!(json["types"][3]["type"], "Status");
+        assert_eq!(json["modules"][0]["instability"], 1.0);
+        assert_eq!(json["most_depended_upon"][0], "domain::Customer");
+    }
+}
@@ -1,36 +1,117 @@
-#[derive(Debug, Eq, PartialEq)]
+/// Elements and modules collected from a crate or a single file
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Model {
+    pub modules: Vec<Module>,
+    pub elements: Vec<Element>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Module {
+    /// Path from the crate root, empty for the root itself
+    pub path: Vec<String>,
+    pub vis: Visibility,
+    pub attrs: Attributes,
+    /// `use` declarations, one per imported path (`use a::{B, C}` gives two)
+    pub uses: Vec<Use>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Use {
+    pub vis: Visibility,
+    /// Segments as written, `*` for globs: `crate::domain::Order`, `super::*`.
+    /// Renamed imports (`a::B as C`) keep the original path
+    pub path: Vec<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Element {
     Struct(StructElement),
     Enum(EnumElement),
+    Trait(TraitElement),
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct StructElement {
     pub name: String,
+    /// Path of the declaring module from the crate root, empty for top-level items
+    pub module: Vec<String>,
+    pub vis: Visibility,
+    pub attrs: Attributes,
+    pub generics: Generics,
     pub fields: Vec<Field>,
+    /// `struct Marker;`, without braces or parentheses
+    pub unit: bool,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct EnumElement {
     pub name: String,
+    pub module: Vec<String>,
+    pub vis: Visibility,
+    pub attrs: Attributes,
+    pub generics: Generics,
     pub variants: Vec<Variant>,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+/// Traits have no fields, they are nodes so that `dyn Trait` and bounds can point at them
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct TraitElement {
+    pub name: String,
+    pub module: Vec<String>,
+    pub vis: Visibility,
+    pub attrs: Attributes,
+    pub generics: Generics,
+    /// Names of the declared associated types (`type Key;`)
+    pub associated_types: Vec<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Variant {
     pub name: String,
+    pub attrs: Attributes,
     pub fields: Vec<Field>,
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum Type {
     Simple(String),
+    /// Name of a generic type parameter of the enclosing element (`K` in `struct Cache<K>`)
+    TypeParam(String),
     Vec(Box<Type>),
+    /// Instantiation of a generic type other than `Vec`: `Option<T>`, `HashMap<K, V>`, `Wrapper<Inner>`
+    Generic(String, Vec<Type>),
     Tuple(Vec<Type>),
+    /// Reference to the inner type: `&'a T`, `&mut T`
     Other(Box<Type>),
+    /// Raw pointer to the inner type: `*const T`, `*mut T`
+    Pointer {
+        mutable: bool,
+        inner: Box<Type>,
+    },
+    /// `dyn Sink + Send`, every bound is a trait path
+    TraitObject(Vec<Type>),
+    /// `impl Iterator<Item = u8>`, every bound is a trait path
+    ImplTrait(Vec<Type>),
+    /// `fn(&Request) -> Response`. Closure traits used as bounds keep their name
+    /// (`Fn(Event) -> Reply` has `name: Some("Fn")`), function pointers have `None`
+    FnPointer {
+        name: Option<String>,
+        inputs: Vec<Type>,
+        output: Option<Box<Type>>,
+    },
+    /// Associated type: `<Backend as Storage>::Key`, or `T::Item` where the trait is not written
+    /// out and `trait_name` is `None`
+    Associated {
+        self_ty: Box<Type>,
+        trait_name: Option<String>,
+        name: String,
+    },
+    /// String a field always holds rather than a type: the tag value `serde_view` adds to
+    /// internally and adjacently tagged variants. Never linked to an element
+    Literal(String),
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Field {
     /// Can be option because of enums:
     /// ```no_run
@@ -38,7 +119,95 @@ pub struct Field {
     ///     Variant1(u32)
     /// }
     /// ```
-    /// In that case name will placed in  Variant struct and Field.name will be empty
+    /// In that case name will placed in  Variant struct and Field.name will be empty.
+    /// Same for tuple structs (`struct Meters(f64)`), see `Field::label`
     pub name: Option<String>,
+    /// Fields of enum variants have no visibility of their own and are `Private` here
+    pub vis: Visibility,
+    pub attrs: Attributes,
     pub ty: Type,
+    /// Types of `ty` written with a module or crate prefix, as written with the type name last:
+    /// `["db", "Row"]` for `Vec<db::Row>`. `Type` only keeps the last segment
+    pub paths: Vec<Vec<String>>,
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub enum Visibility {
+    Public,
+    /// `pub(crate)`
+    Crate,
+    /// `pub(super)`, `pub(self)` or `pub(in path)`, with the text inside the parentheses
+    Restricted(String),
+    #[default]
+    Private,
+}
+
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Attributes {
+    /// Traits listed in `#[derive(...)]`
+    pub derives: Vec<String>,
+    /// Every other attribute except docs, as source text without `#[...]`: `serde(rename = "id")`
+    pub other: Vec<String>,
+    /// Text of `///` comments and `#[doc = ...]`, lines joined with `\n`
+    pub docs: Option<String>,
+}
+
+/// Weight of a graph edge, pointing from the element that owns the field
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Edge {
+    pub kind: EdgeKind,
+    /// Field that produced the edge: `field` or `.0` for structs, `Variant.field` or `Variant.0` for enums
+    pub field: Option<String>,
+}
+
+/// Edge kinds follow UML template binding for instantiations of local generic types.
+/// For a field `inner: Wrapper<Inner>` the graph gets `Field` to `Wrapper` and `BoundWith` to
+/// `Inner`, or, with synthesized bindings, `Field` to a `Wrapper<Inner>` node which has
+/// `Binding` to `Wrapper` and `BoundWith` to `Inner`
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum EdgeKind {
+    Field,
+    BoundWith,
+    Binding,
+    /// Field whose type is an associated type declared by a local trait, points at the trait
+    AssociatedType,
+}
+
+/// Generic parameters and where-clause of a struct or enum:
+/// ```no_run
+/// use std::hash::Hash;
+///
+/// struct Cache<'a, K: Hash, V, const N: usize> where V: Clone {
+///     keys: [&'a K; N],
+///     values: Vec<V>,
+/// }
+/// ```
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Generics {
+    pub params: Vec<GenericParam>,
+    pub where_clause: Vec<WherePredicate>,
+}
+
+/// Bounds, defaults and const types are kept as their source text (`Hash + Eq`, `usize`)
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum GenericParam {
+    Lifetime {
+        name: String,
+        bounds: Vec<String>,
+    },
+    Type {
+        name: String,
+        bounds: Vec<String>,
+        default: Option<String>,
+    },
+    Const {
+        name: String,
+        ty: String,
+    },
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct WherePredicate {
+    pub bounded: String,
+    pub bounds: Vec<String>,
 }
@@ -0,0 +1,60 @@
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// A crate directory with `src/lib.rs` and two modules, unique per test
+fn crate_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("rcvis-{}-{}", name, std::process::id()));
+    fs::create_dir_all(dir.join("src")).unwrap();
+    fs::write(dir.join("src/lib.rs"), "mod domain;\nmod shop;\n").unwrap();
+    fs::write(
+        dir.join("src/domain.rs"),
+        "pub struct Order {\n    pub customer: crate::shop::Customer,\n}\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("src/shop.rs"),
+        "pub struct Customer {\n    pub name: String,\n}\n",
+    )
+    .unwrap();
+    dir
+}
+
+fn rcvis(args: &[&str], dir: &PathBuf) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_rcvis"))
+        .args(args)
+        .current_dir(dir)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_check_on_directory() {
+    let dir = crate_dir("check");
+    fs::write(
+        dir.join("rules.toml"),
+        "[[rule]]\nfrom = \"domain\"\nforbid = [\"shop\"]\n",
+    )
+    .unwrap();
+    let output = rcvis(&["check", "--rules", "rules.toml", "."], &dir);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(1), "{:?}", output);
+    assert!(
+        stdout.starts_with("./src/domain.rs:2: domain::Order.customer -> shop::Customer"),
+        "{}",
+        stdout
+    );
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_uses_on_directory() {
+    let dir = crate_dir("uses");
+    let output = rcvis(&["uses", "Customer", "."], &dir);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(output.status.success(), "{:?}", output);
+    assert_eq!(stdout, "./src/domain.rs:2: Order.customer: Customer\n");
+    fs::remove_dir_all(dir).unwrap();
+}